@@ -0,0 +1,205 @@
+use std::collections::HashMap;
+use std::env;
+use std::sync::OnceLock;
+
+use serde::{Deserialize, Serialize};
+
+use crate::errors::FluffError;
+use crate::services::aws::parameter_store::{self, ReadOptions};
+use crate::services::aws::{BUCKET_PREPROD, BUCKET_PROD, TABLE_EPHEMERAL, TABLE_USERS};
+
+static SHARED_CONFIG: OnceLock<FluffConfig> = OnceLock::new();
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Stage {
+    Prod,
+    Preprod,
+    Dev,
+    Local,
+}
+
+impl Stage {
+    pub fn parse(stage: &str) -> Result<Stage, FluffError> {
+        match stage.to_lowercase().as_str() {
+            "prod" | "production" => Ok(Stage::Prod),
+            "preprod" | "staging" => Ok(Stage::Preprod),
+            "dev" | "development" => Ok(Stage::Dev),
+            "local" => Ok(Stage::Local),
+            _ => Err(FluffError::new_u16(
+                500,
+                "InvalidStage",
+                "FLUFF_STAGE must be one of prod, preprod, dev or local",
+                false,
+            )
+            .add_context(stage)),
+        }
+    }
+
+    // Reads FLUFF_STAGE, which is required so that a misconfigured deployment never uses production resources
+    pub fn from_env() -> Result<Stage, FluffError> {
+        match env::var("FLUFF_STAGE") {
+            Ok(stage) => Stage::parse(&stage),
+            Err(_) => Err(FluffError::new_u16(
+                500,
+                "StageMissing",
+                "Missing FLUFF_STAGE in environment variables, set it to prod to keep the production resources",
+                false,
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Prod => "prod",
+            Stage::Preprod => "preprod",
+            Stage::Dev => "dev",
+            Stage::Local => "local",
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FluffConfig {
+    pub stage: Stage,
+    pub table_users: String,
+    pub table_ephemeral: String,
+    pub bucket: String,
+    pub issuer: String,
+    pub audience: String,
+    pub twitch_redirect_uri: String,
+}
+
+// Fields of the configuration, with the environment variable overriding each of them
+const FIELDS: [(&str, &str); 6] = [
+    ("table_users", "FLUFF_TABLE_USERS"),
+    ("table_ephemeral", "FLUFF_TABLE_EPHEMERAL"),
+    ("bucket", "FLUFF_BUCKET"),
+    ("issuer", "FLUFF_ISSUER"),
+    ("audience", "FLUFF_AUDIENCE"),
+    ("twitch_redirect_uri", "TWITCH_REDIRECT_URI"),
+];
+
+// Values known for a stage, the others must come from Parameter Store or the environment
+fn stage_defaults(stage: Stage) -> HashMap<&'static str, String> {
+    let defaults = match stage {
+        Stage::Prod => vec![
+            ("table_users", TABLE_USERS),
+            ("table_ephemeral", TABLE_EPHEMERAL),
+            ("bucket", BUCKET_PROD),
+            ("issuer", "https://auth.fluffevent.fr"),
+            ("audience", "fluffevent.fr"),
+            ("twitch_redirect_uri", "https://fluffevent.fr"),
+        ],
+        Stage::Preprod => vec![("bucket", BUCKET_PREPROD)],
+        Stage::Dev | Stage::Local => vec![],
+    };
+    defaults.into_iter().map(|(field, value)| (field, String::from(value))).collect()
+}
+
+impl FluffConfig {
+    fn from_values(stage: Stage, mut values: HashMap<&'static str, String>) -> Result<FluffConfig, FluffError> {
+        let missing: Vec<String> = FIELDS
+            .iter()
+            .filter(|(field, _)| !values.contains_key(field))
+            .map(|(field, variable)| format!("{} ({})", field, variable))
+            .collect();
+        if !missing.is_empty() {
+            return Err(FluffError::new_u16(
+                500,
+                "MissingConfig",
+                "Configuration values are missing for the current stage",
+                false,
+            )
+            .add_context(stage.name())
+            .add_context(&missing.join(", ")));
+        }
+
+        let mut take = |field: &str| values.remove(field).unwrap_or_default();
+        Ok(FluffConfig {
+            stage,
+            table_users: take("table_users"),
+            table_ephemeral: take("table_ephemeral"),
+            bucket: take("bucket"),
+            issuer: take("issuer"),
+            audience: take("audience"),
+            twitch_redirect_uri: take("twitch_redirect_uri"),
+        })
+    }
+
+    // Stage defaults overridden by environment variables
+    pub fn from_env() -> Result<FluffConfig, FluffError> {
+        let stage = Stage::from_env()?;
+        let mut values = stage_defaults(stage);
+        apply_env(&mut values);
+        FluffConfig::from_values(stage, values)
+    }
+
+    // Stage defaults overridden by `/fluff/<stage>/config/<field>` parameters, then by environment variables
+    pub async fn load() -> Result<FluffConfig, FluffError> {
+        let stage = Stage::from_env()?;
+        let mut values = stage_defaults(stage);
+        let path = format!("/fluff/{}/config/", stage.name());
+        let options = ReadOptions::new().decrypt(true);
+        for parameter in parameter_store::get_parameters(&path, &options).await? {
+            let field = FIELDS
+                .iter()
+                .map(|(field, _)| *field)
+                .find(|field| parameter.name.strip_prefix(&path) == Some(*field));
+            if let Some(field) = field {
+                values.insert(field, parameter.value);
+            }
+        }
+        apply_env(&mut values);
+        FluffConfig::from_values(stage, values)
+    }
+
+    // Returns the configuration shared by the whole Lambda container, read from the environment on first use
+    pub fn shared() -> Result<&'static FluffConfig, FluffError> {
+        if let Some(config) = SHARED_CONFIG.get() {
+            return Ok(config);
+        }
+        let config = FluffConfig::from_env()?;
+        Ok(SHARED_CONFIG.get_or_init(|| config))
+    }
+
+    // Replaces the default shared configuration, e.g. with the result of `FluffConfig::load`
+    pub fn install(config: FluffConfig) -> Result<(), FluffError> {
+        SHARED_CONFIG.set(config).map_err(|_| {
+            FluffError::new_u16(
+                500,
+                "ConfigAlreadyInitialized",
+                "The shared configuration is already initialized",
+                false,
+            )
+        })
+    }
+}
+
+fn apply_env(values: &mut HashMap<&'static str, String>) {
+    for (field, variable) in FIELDS {
+        if let Ok(value) = env::var(variable) {
+            values.insert(field, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{stage_defaults, FluffConfig, Stage};
+
+    #[test]
+    fn prod_has_every_value() {
+        let config = FluffConfig::from_values(Stage::Prod, stage_defaults(Stage::Prod)).unwrap();
+        assert_eq!(config.table_users, "Fluff-Users");
+        assert_eq!(config.bucket, "fluffevent-data-prod");
+    }
+
+    #[test]
+    fn other_stages_require_explicit_values() {
+        let err = FluffConfig::from_values(Stage::Preprod, stage_defaults(Stage::Preprod)).unwrap_err();
+        assert_eq!(err.error_name, "MissingConfig");
+        assert!(err.context[1].contains("table_users (FLUFF_TABLE_USERS)"));
+        assert!(!err.context[1].contains("bucket"));
+    }
+}
@@ -1,3 +1,4 @@
+pub mod config;
 pub mod errors;
 pub mod common_responses;
 pub mod models;
@@ -1,18 +1,26 @@
-use std::collections::HashMap;
 use aws_sdk_dynamodb::types::AttributeValue;
+use serde::{Deserialize, Serialize};
 
 use crate::errors::FluffError;
 use crate::models::user_jwt::UserJWT;
-use crate::services::aws::dynamodb::DynamoItem;
-use crate::services::aws::TABLE_USERS;
+use crate::services::aws::dynamodb::serialization::string_set;
+use crate::services::aws::dynamodb::{Condition, PrimaryKey};
+use crate::config::FluffConfig;
+use crate::services::repository::{DynamoRepository, Repository};
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct User {
     pub id: String,
     pub username: String,
     pub display_name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub email: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub profile_picture: Option<String>,
+    #[serde(default, with = "string_set")]
     pub permissions: Vec<String>,
+    #[serde(default)]
+    pub version: u64,
 }
 
 impl User {
@@ -24,54 +32,139 @@ impl User {
             email: None,
             profile_picture: Some(jwt.picture.clone()),
             permissions: jwt.scope.clone(),
+            version: 0,
         }
     }
 
-    fn from_dynamo_item(item: DynamoItem) -> Result<User, FluffError> {
-        let id = item.get_string("id")?;
-        let username = item.get_string("username")?;
-        let display_name = item.get_string("display_name")?;
-        let email = item.get_string_opt("email");
-        let profile_picture = item.get_string_opt("profile_picture");
-        let permissions = item.get_strings_vec("permissions")?;
-
-        Ok(User {
-            id,
-            username,
-            display_name,
-            email,
-            profile_picture,
-            permissions,
-        })
+    pub fn repository() -> Result<DynamoRepository<User>, FluffError> {
+        Ok(DynamoRepository::new(&FluffConfig::shared()?.table_users))
     }
 
-    fn into_dynamo_hashmap(&self) -> HashMap<String, AttributeValue> {
-        let mut item = HashMap::new();
-        item.insert("id".to_string(), AttributeValue::S(self.id.clone()));
-        item.insert("username".to_string(), AttributeValue::S(self.username.clone()));
-        item.insert("display_name".to_string(), AttributeValue::S(self.display_name.clone()));
-        if let Some(email) = &self.email {
-            item.insert("email".to_string(), AttributeValue::S(email.clone()));
-        }
-        if let Some(profile_picture) = &self.profile_picture {
-            item.insert("profile_picture".to_string(), AttributeValue::S(profile_picture.clone()));
-        }
-        item.insert("permissions".to_string(), AttributeValue::Ss(self.permissions.clone()));
-        item
+    fn key(id: &str) -> PrimaryKey {
+        PrimaryKey::string("id", id)
     }
 
     pub async fn from_db(id: String) -> Result<User, FluffError> {
-        let mut query = HashMap::new();
-        query.insert("id".to_string(), AttributeValue::S(id));
+        User::from_repository(&User::repository()?, &id).await
+    }
 
-        let item = crate::services::aws::dynamodb::get_item(TABLE_USERS, query, false).await?;
+    pub async fn from_repository(repository: &impl Repository<User>, id: &str) -> Result<User, FluffError> {
+        repository.get(&User::key(id)).await?.ok_or_else(|| {
+            FluffError::new_u16(404, "ItemNotFound", "Item not found in database or expired", false)
+                .add_context(id)
+        })
+    }
+
+    // Returns the deleted user, or None if there was no user with this id
+    pub async fn delete_from_db(id: String) -> Result<Option<User>, FluffError> {
+        User::delete_from_repository(&User::repository()?, &id).await
+    }
 
-        User::from_dynamo_item(item)
+    pub async fn delete_from_repository(repository: &impl Repository<User>, id: &str) -> Result<Option<User>, FluffError> {
+        repository.delete(&User::key(id), &Condition::new()).await
     }
 
+    // Creates or replaces the user, whatever is stored
     pub async fn to_db(&self) -> Result<bool, FluffError> {
-        let item = self.into_dynamo_hashmap();
+        self.to_repository(&User::repository()?).await
+    }
+
+    pub async fn to_repository(&self, repository: &impl Repository<User>) -> Result<bool, FluffError> {
+        repository.put(self, &Condition::new()).await?;
+        Ok(true)
+    }
+
+    // Fails with a 409 ConditionalCheckFailed if a user with the same id already exists
+    pub async fn to_db_create(&mut self) -> Result<bool, FluffError> {
+        self.to_repository_create(&User::repository()?).await
+    }
+
+    pub async fn to_repository_create(&mut self, repository: &impl Repository<User>) -> Result<bool, FluffError> {
+        let condition = Condition::new().attribute_not_exists("id");
+
+        self.write_with_version(repository, 1, &condition).await
+    }
+
+    // Fails with a 409 ConditionalCheckFailed if the user was modified since it was read
+    pub async fn to_db_versioned(&mut self) -> Result<bool, FluffError> {
+        self.to_repository_versioned(&User::repository()?).await
+    }
+
+    pub async fn to_repository_versioned(&mut self, repository: &impl Repository<User>) -> Result<bool, FluffError> {
+        let condition = match self.version {
+            0 => Condition::new()
+                .attribute_exists("id")
+                .attribute_not_exists("version"),
+            version => Condition::new()
+                .attribute_exists("id")
+                .equals("version", AttributeValue::N(version.to_string())),
+        };
+
+        self.write_with_version(repository, self.version + 1, &condition).await
+    }
+
+    async fn write_with_version(
+        &mut self,
+        repository: &impl Repository<User>,
+        version: u64,
+        condition: &Condition,
+    ) -> Result<bool, FluffError> {
+        let user = User {
+            version,
+            ..self.clone()
+        };
+
+        repository.put(&user, condition).await?;
+        self.version = version;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::User;
+    use crate::services::repository::MemoryRepository;
+
+    fn user() -> User {
+        User {
+            id: String::from("42"),
+            username: String::from("fluff"),
+            display_name: String::from("Fluff"),
+            email: None,
+            profile_picture: None,
+            permissions: vec![],
+            version: 0,
+        }
+    }
+
+    #[test]
+    fn plain_write_replaces_an_existing_user() {
+        let repository = MemoryRepository::new(&["id"]);
+        let mut stored = user();
+        block_on(stored.to_repository_create(&repository)).unwrap();
+
+        // A user rebuilt from a JWT has version 0 but must still be saved on login
+        let fresh = User {
+            display_name: String::from("New name"),
+            ..user()
+        };
+        assert!(block_on(fresh.to_repository(&repository)).unwrap());
+        assert_eq!(block_on(User::from_repository(&repository, "42")).unwrap().display_name, "New name");
+    }
+
+    #[test]
+    fn create_and_versioned_writes_are_conditional() {
+        let repository = MemoryRepository::new(&["id"]);
+        let mut first = user();
+        block_on(first.to_repository_create(&repository)).unwrap();
+        assert_eq!(first.version, 1);
+        assert_eq!(block_on(user().to_repository_create(&repository)).unwrap_err().http_code, 409);
 
-        crate::services::aws::dynamodb::insert_item(TABLE_USERS, item).await
+        let mut stale = first.clone();
+        block_on(first.to_repository_versioned(&repository)).unwrap();
+        assert_eq!(first.version, 2);
+        assert_eq!(block_on(stale.to_repository_versioned(&repository)).unwrap_err().http_code, 409);
     }
 }
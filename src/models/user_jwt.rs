@@ -2,6 +2,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
 use jsonwebtoken;
 
+use crate::config::FluffConfig;
 use crate::errors::FluffError;
 use crate::models::user::User;
 
@@ -36,11 +37,12 @@ impl UserJWT {
             })?
             .as_secs();
         let validity: u64 = 60 * 60 * 24 * 7;
+        let config = FluffConfig::shared()?;
 
         Ok(UserJWT {
-            iss: String::from("https://auth.fluffevent.fr"),
+            iss: config.issuer.clone(),
             sub: user.id.clone(),
-            aud: vec![String::from("fluffevent.fr")],
+            aud: vec![config.audience.clone()],
             nbf: now_as_sec - 300,
             exp: now_as_sec + validity,
             iat: now_as_sec,
@@ -77,8 +79,9 @@ impl UserJWT {
         })?;
     
         let mut validating = jsonwebtoken::Validation::new(jsonwebtoken::Algorithm::RS512);
-        validating.set_issuer(&["https://auth.fluffevent.fr"]);
-        validating.set_audience(&["fluffevent.fr"]);
+        let config = FluffConfig::shared()?;
+        validating.set_issuer(&[&config.issuer]);
+        validating.set_audience(&[&config.audience]);
         validating.leeway = 300;
         validating.validate_exp = true;
         validating.validate_nbf = true;
@@ -1,3 +1,6 @@
 pub mod aws;
+pub mod cache;
+pub mod object_store;
+pub mod repository;
 pub mod rsa_keys;
 pub mod twitch;
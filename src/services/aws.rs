@@ -1,9 +1,33 @@
+pub mod context;
 pub mod dynamodb;
 pub mod s3;
 pub mod parameter_store;
+pub mod secrets_manager;
 
+use aws_sdk_dynamodb::types::ScalarAttributeType;
 
+use crate::config::FluffConfig;
+use crate::errors::FluffError;
+use dynamodb::{KeyAttribute, TableSchema};
+
+// Production names, `FluffConfig` holds the names of the current stage
 pub static TABLE_USERS: &str = "Fluff-Users";
+pub static TABLE_EPHEMERAL: &str = "Fluff-Ephemeral";
+
+pub fn users_table_schema(config: &FluffConfig) -> TableSchema {
+    TableSchema::new(&config.table_users, KeyAttribute::new("id", ScalarAttributeType::S))
+}
+
+pub fn ephemeral_table_schema(config: &FluffConfig) -> TableSchema {
+    TableSchema::new(&config.table_ephemeral, KeyAttribute::new("id", ScalarAttributeType::S))
+        .ttl_attribute(dynamodb::TTL_ATTRIBUTE)
+}
+
+// Every table used by the crate in the current stage, used to bootstrap a local environment
+pub fn table_schemas() -> Result<Vec<TableSchema>, FluffError> {
+    let config = FluffConfig::shared()?;
+    Ok(vec![users_table_schema(config), ephemeral_table_schema(config)])
+}
 
 pub static BUCKET_PREPROD: &str = "fluffevent-data-preprod";
 pub static BUCKET_PROD: &str = "fluffevent-data-prod";
@@ -0,0 +1,185 @@
+use aws_config::{BehaviorVersion, Region, SdkConfig};
+use aws_sdk_dynamodb::config::Credentials;
+use tokio::sync::OnceCell;
+
+use crate::errors::FluffError;
+
+static SHARED_CONTEXT: OnceCell<FluffContext> = OnceCell::const_new();
+
+pub struct FluffContext {
+    config: SdkConfig,
+    dynamodb: aws_sdk_dynamodb::Client,
+    s3: aws_sdk_s3::Client,
+    secrets_manager: aws_sdk_secretsmanager::Client,
+    ssm: aws_sdk_ssm::Client,
+    s3_endpoint_url: Option<String>,
+}
+
+impl FluffContext {
+    pub async fn load() -> FluffContext {
+        FluffContext::builder().build().await
+    }
+
+    pub fn builder() -> FluffContextBuilder {
+        FluffContextBuilder::default()
+    }
+
+    pub fn from_sdk_config(config: SdkConfig) -> FluffContext {
+        FluffContext::from_sdk_config_int(config, &ServiceEndpoints::default(), false)
+    }
+
+    fn from_sdk_config_int(config: SdkConfig, endpoints: &ServiceEndpoints, force_path_style: bool) -> FluffContext {
+        let mut dynamodb_config = aws_sdk_dynamodb::config::Builder::from(&config);
+        if let Some(endpoint_url) = &endpoints.dynamodb {
+            dynamodb_config = dynamodb_config.endpoint_url(endpoint_url);
+        }
+        let mut s3_config = aws_sdk_s3::config::Builder::from(&config).force_path_style(force_path_style);
+        if let Some(endpoint_url) = &endpoints.s3 {
+            s3_config = s3_config.endpoint_url(endpoint_url);
+        }
+        let mut secrets_manager_config = aws_sdk_secretsmanager::config::Builder::from(&config);
+        if let Some(endpoint_url) = &endpoints.secrets_manager {
+            secrets_manager_config = secrets_manager_config.endpoint_url(endpoint_url);
+        }
+        let mut ssm_config = aws_sdk_ssm::config::Builder::from(&config);
+        if let Some(endpoint_url) = &endpoints.ssm {
+            ssm_config = ssm_config.endpoint_url(endpoint_url);
+        }
+
+        FluffContext {
+            dynamodb: aws_sdk_dynamodb::Client::from_conf(dynamodb_config.build()),
+            s3: aws_sdk_s3::Client::from_conf(s3_config.build()),
+            secrets_manager: aws_sdk_secretsmanager::Client::from_conf(secrets_manager_config.build()),
+            ssm: aws_sdk_ssm::Client::from_conf(ssm_config.build()),
+            s3_endpoint_url: endpoints.s3.clone().or_else(|| config.endpoint_url().map(String::from)),
+            config,
+        }
+    }
+
+    // Returns the context shared by the whole Lambda container, loading it on first use
+    pub async fn shared() -> &'static FluffContext {
+        SHARED_CONTEXT.get_or_init(FluffContext::load).await
+    }
+
+    // Replaces the default shared context, must be called before any AWS helper is used
+    pub fn install(context: FluffContext) -> Result<(), FluffError> {
+        SHARED_CONTEXT.set(context).map_err(|_| {
+            FluffError::new_u16(
+                500,
+                "ContextAlreadyInitialized",
+                "The shared AWS context is already initialized",
+                false,
+            )
+        })
+    }
+
+    pub fn sdk_config(&self) -> &SdkConfig {
+        &self.config
+    }
+
+    pub fn dynamodb(&self) -> &aws_sdk_dynamodb::Client {
+        &self.dynamodb
+    }
+
+    pub fn s3(&self) -> &aws_sdk_s3::Client {
+        &self.s3
+    }
+
+    // Custom endpoint used by the S3 client, None when it talks to AWS
+    pub fn s3_endpoint_url(&self) -> Option<&str> {
+        self.s3_endpoint_url.as_deref()
+    }
+
+    pub fn secrets_manager(&self) -> &aws_sdk_secretsmanager::Client {
+        &self.secrets_manager
+    }
+
+    pub fn ssm(&self) -> &aws_sdk_ssm::Client {
+        &self.ssm
+    }
+}
+
+// Endpoints overriding the shared one for a single service
+#[derive(Default)]
+struct ServiceEndpoints {
+    dynamodb: Option<String>,
+    s3: Option<String>,
+    secrets_manager: Option<String>,
+    ssm: Option<String>,
+}
+
+#[derive(Default)]
+pub struct FluffContextBuilder {
+    endpoint_url: Option<String>,
+    endpoints: ServiceEndpoints,
+    region: Option<String>,
+    credentials: Option<Credentials>,
+    force_path_style: bool,
+}
+
+impl FluffContextBuilder {
+    // Used by every service without its own endpoint
+    pub fn endpoint_url(mut self, endpoint_url: &str) -> Self {
+        self.endpoint_url = Some(String::from(endpoint_url));
+        self
+    }
+
+    // e.g. DynamoDB Local while the other services stay on AWS
+    pub fn dynamodb_endpoint_url(mut self, endpoint_url: &str) -> Self {
+        self.endpoints.dynamodb = Some(String::from(endpoint_url));
+        self
+    }
+
+    pub fn s3_endpoint_url(mut self, endpoint_url: &str) -> Self {
+        self.endpoints.s3 = Some(String::from(endpoint_url));
+        self
+    }
+
+    pub fn secrets_manager_endpoint_url(mut self, endpoint_url: &str) -> Self {
+        self.endpoints.secrets_manager = Some(String::from(endpoint_url));
+        self
+    }
+
+    pub fn ssm_endpoint_url(mut self, endpoint_url: &str) -> Self {
+        self.endpoints.ssm = Some(String::from(endpoint_url));
+        self
+    }
+
+    pub fn region(mut self, region: &str) -> Self {
+        self.region = Some(String::from(region));
+        self
+    }
+
+    pub fn credentials(mut self, access_key_id: &str, secret_access_key: &str) -> Self {
+        self.credentials = Some(Credentials::new(
+            access_key_id,
+            secret_access_key,
+            None,
+            None,
+            "fluff-lib",
+        ));
+        self
+    }
+
+    // Needed by most local S3 stand-ins which do not support virtual-hosted buckets
+    pub fn force_path_style(mut self, force_path_style: bool) -> Self {
+        self.force_path_style = force_path_style;
+        self
+    }
+
+    pub async fn build(self) -> FluffContext {
+        let mut loader = aws_config::defaults(BehaviorVersion::latest());
+        if let Some(endpoint_url) = self.endpoint_url {
+            loader = loader.endpoint_url(endpoint_url);
+        }
+        if let Some(region) = self.region {
+            loader = loader.region(Region::new(region));
+        }
+        if let Some(credentials) = self.credentials {
+            loader = loader.credentials_provider(credentials);
+        }
+        let config = loader.load().await;
+
+        FluffContext::from_sdk_config_int(config, &self.endpoints, self.force_path_style)
+    }
+}
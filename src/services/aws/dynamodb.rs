@@ -1,63 +1,410 @@
 use std::collections::HashMap;
-use aws_config::BehaviorVersion;
-use aws_sdk_dynamodb::types::AttributeValue;
+use std::str::FromStr;
+use std::time::Duration;
+use aws_sdk_dynamodb::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::{AttributeValue, ReturnValue};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
 
+pub mod batch;
+pub mod expiring;
+pub mod expression;
+pub mod key;
+pub mod query;
+pub mod schema;
+pub mod serialization;
+pub mod transaction;
+
+use expression::ExpressionAttributes;
+pub use batch::{batch_get_items, batch_write_items, BatchWrite};
+pub use expiring::{insert_item_with_ttl, ExpiringStore, TTL_ATTRIBUTE};
+pub use expression::{AttributePath, Condition, Update};
+pub use key::{KeyValue, PrimaryKey};
+pub use query::{query, query_stream, scan, scan_stream, QueryRequest, ReadRequest, ScanRequest, SortKeyCondition};
+pub use schema::{create_missing_tables, create_table, GlobalIndex, KeyAttribute, TableSchema};
+pub use transaction::{transact_write, TransactWrite, TransactionError};
+
+#[derive(Debug, Clone, Default, PartialEq)]
 pub struct DynamoItem {
     data: HashMap<String, AttributeValue>
 }
 
+impl From<HashMap<String, AttributeValue>> for DynamoItem {
+    fn from(data: HashMap<String, AttributeValue>) -> Self {
+        DynamoItem { data }
+    }
+}
+
+impl From<DynamoItem> for HashMap<String, AttributeValue> {
+    fn from(item: DynamoItem) -> Self {
+        item.data
+    }
+}
+
+fn key_not_found(key: &str) -> FluffError {
+    FluffError::new_u16(500, "DatabaseError", "Key not found in database item", true)
+        .add_context(key)
+}
+
+fn wrong_type(key: &str, description: &str) -> FluffError {
+    FluffError::new_u16(500, "DatabaseError", description, true)
+        .add_context(key)
+}
+
 impl DynamoItem {
-    pub fn get_string(&self, key: &str) -> Result<String, FluffError> {
+    pub fn new() -> DynamoItem {
+        DynamoItem::default()
+    }
+
+    pub fn from_struct<T: Serialize + ?Sized>(value: &T) -> Result<DynamoItem, FluffError> {
+        serialization::to_item(value).map(|data| DynamoItem { data })
+    }
+
+    pub fn into_struct<T: DeserializeOwned>(self) -> Result<T, FluffError> {
+        serialization::from_item(self.data)
+    }
+
+    pub fn into_hashmap(self) -> HashMap<String, AttributeValue> {
+        self.data
+    }
+
+    pub fn as_hashmap(&self) -> &HashMap<String, AttributeValue> {
+        &self.data
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.data.contains_key(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&AttributeValue> {
         self.data.get(key)
-            .ok_or(FluffError::new_u16(500, "DatabaseError", "Key not found in database item", true))
-            .and_then(|v| v.as_s()
-                .or(Err(FluffError::new_u16(500, "DatabaseError", "Value is not a string", true)))
-            ).map(|v| v.clone())
     }
-    
+
+    fn get_value(&self, key: &str) -> Result<&AttributeValue, FluffError> {
+        self.data.get(key).ok_or_else(|| key_not_found(key))
+    }
+
+    fn get_number<T: FromStr>(&self, key: &str, description: &str) -> Result<T, FluffError> {
+        self.get_value(key)?
+            .as_n()
+            .map_err(|_| wrong_type(key, "Value is not a number"))?
+            .parse()
+            .map_err(|_| wrong_type(key, description))
+    }
+
+    pub fn get_string(&self, key: &str) -> Result<String, FluffError> {
+        self.get_value(key)?
+            .as_s()
+            .cloned()
+            .map_err(|_| wrong_type(key, "Value is not a string"))
+    }
+
     pub fn get_string_opt(&self, key: &str) -> Option<String> {
-        self.data.get(key)
-            .and_then(|v| v.as_s().ok())
-            .map(|v| v.clone())
+        self.get_string(key).ok()
     }
 
+    // An empty set is stored as NULL and read back as an empty vector
     pub fn get_strings_vec(&self, key: &str) -> Result<Vec<String>, FluffError> {
-        self.data.get(key)
-            .ok_or(FluffError::new_u16(500, "DatabaseError", "Key not found in database item", true))
-            .and_then(|v| v.as_ss()
-                .or(Err(FluffError::new_u16(500, "DatabaseError", "Value is not a string set", true)))
-            ).map(|v| v.clone())
+        let value = self.get_value(key)?;
+        if value.is_null() {
+            return Ok(vec![]);
+        }
+        value
+            .as_ss()
+            .cloned()
+            .map_err(|_| wrong_type(key, "Value is not a string set"))
+    }
+
+    pub fn get_strings_vec_opt(&self, key: &str) -> Option<Vec<String>> {
+        self.get_strings_vec(key).ok()
+    }
+
+    pub fn get_i64(&self, key: &str) -> Result<i64, FluffError> {
+        self.get_number(key, "Value is not a signed 64-bit integer")
+    }
+
+    pub fn get_i64_opt(&self, key: &str) -> Option<i64> {
+        self.get_i64(key).ok()
+    }
+
+    pub fn get_u64(&self, key: &str) -> Result<u64, FluffError> {
+        self.get_number(key, "Value is not an unsigned 64-bit integer")
+    }
+
+    pub fn get_u64_opt(&self, key: &str) -> Option<u64> {
+        self.get_u64(key).ok()
+    }
+
+    pub fn get_f64(&self, key: &str) -> Result<f64, FluffError> {
+        self.get_number(key, "Value is not a floating point number")
+    }
+
+    pub fn get_f64_opt(&self, key: &str) -> Option<f64> {
+        self.get_f64(key).ok()
+    }
+
+    pub fn get_bool(&self, key: &str) -> Result<bool, FluffError> {
+        self.get_value(key)?
+            .as_bool()
+            .copied()
+            .map_err(|_| wrong_type(key, "Value is not a boolean"))
+    }
+
+    pub fn get_bool_opt(&self, key: &str) -> Option<bool> {
+        self.get_bool(key).ok()
+    }
+
+    pub fn get_map(&self, key: &str) -> Result<DynamoItem, FluffError> {
+        self.get_value(key)?
+            .as_m()
+            .map(|v| DynamoItem { data: v.clone() })
+            .map_err(|_| wrong_type(key, "Value is not a map"))
+    }
+
+    pub fn get_map_opt(&self, key: &str) -> Option<DynamoItem> {
+        self.get_map(key).ok()
+    }
+
+    pub fn get_list(&self, key: &str) -> Result<Vec<AttributeValue>, FluffError> {
+        self.get_value(key)?
+            .as_l()
+            .cloned()
+            .map_err(|_| wrong_type(key, "Value is not a list"))
+    }
+
+    pub fn get_list_opt(&self, key: &str) -> Option<Vec<AttributeValue>> {
+        self.get_list(key).ok()
+    }
+
+    pub fn get_bytes(&self, key: &str) -> Result<Vec<u8>, FluffError> {
+        self.get_value(key)?
+            .as_b()
+            .map(|v| v.as_ref().to_vec())
+            .map_err(|_| wrong_type(key, "Value is not a binary"))
+    }
+
+    pub fn get_bytes_opt(&self, key: &str) -> Option<Vec<u8>> {
+        self.get_bytes(key).ok()
+    }
+
+    // An empty set is stored as NULL and read back as an empty vector
+    pub fn get_number_set<T: FromStr>(&self, key: &str) -> Result<Vec<T>, FluffError> {
+        let value = self.get_value(key)?;
+        if value.is_null() {
+            return Ok(vec![]);
+        }
+        value
+            .as_ns()
+            .map_err(|_| wrong_type(key, "Value is not a number set"))?
+            .iter()
+            .map(|v| v.parse().map_err(|_| wrong_type(key, "Number set contains an invalid number")))
+            .collect()
+    }
+
+    pub fn get_number_set_opt<T: FromStr>(&self, key: &str) -> Option<Vec<T>> {
+        self.get_number_set(key).ok()
+    }
+
+    pub fn is_expired(&self) -> bool {
+        expiring::is_expired(&self.data)
+    }
+
+    pub fn set_expires_in(&mut self, ttl: Duration) -> Result<&mut Self, FluffError> {
+        expiring::stamp_expiry(&mut self.data, ttl)?;
+        Ok(self)
+    }
+
+    pub fn insert(&mut self, key: &str, value: AttributeValue) -> &mut Self {
+        self.data.insert(String::from(key), value);
+        self
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<AttributeValue> {
+        self.data.remove(key)
+    }
+
+    pub fn set_string(&mut self, key: &str, value: &str) -> &mut Self {
+        self.insert(key, AttributeValue::S(String::from(value)))
+    }
+
+    // DynamoDB rejects empty sets, so an empty vector is stored as NULL like the serializer does
+    pub fn set_strings_vec(&mut self, key: &str, value: Vec<String>) -> &mut Self {
+        if value.is_empty() {
+            self.set_null(key)
+        } else {
+            self.insert(key, AttributeValue::Ss(value))
+        }
+    }
+
+    pub fn set_i64(&mut self, key: &str, value: i64) -> &mut Self {
+        self.insert(key, AttributeValue::N(value.to_string()))
+    }
+
+    pub fn set_u64(&mut self, key: &str, value: u64) -> &mut Self {
+        self.insert(key, AttributeValue::N(value.to_string()))
+    }
+
+    // Fails on NaN, infinities and numbers outside the range DynamoDB accepts
+    pub fn set_f64(&mut self, key: &str, value: f64) -> Result<&mut Self, FluffError> {
+        let value = serialization::to_attribute_value(&value).map_err(|err| err.add_context(key))?;
+        Ok(self.insert(key, value))
+    }
+
+    pub fn set_bool(&mut self, key: &str, value: bool) -> &mut Self {
+        self.insert(key, AttributeValue::Bool(value))
+    }
+
+    pub fn set_null(&mut self, key: &str) -> &mut Self {
+        self.insert(key, AttributeValue::Null(true))
+    }
+
+    pub fn set_map(&mut self, key: &str, value: DynamoItem) -> &mut Self {
+        self.insert(key, AttributeValue::M(value.data))
+    }
+
+    pub fn set_list(&mut self, key: &str, value: Vec<AttributeValue>) -> &mut Self {
+        self.insert(key, AttributeValue::L(value))
+    }
+
+    pub fn set_bytes(&mut self, key: &str, value: &[u8]) -> &mut Self {
+        self.insert(key, AttributeValue::B(Blob::new(value)))
+    }
+
+    // DynamoDB rejects empty sets, so an empty slice is stored as NULL like the serializer does
+    pub fn set_number_set<T: ToString>(&mut self, key: &str, value: &[T]) -> &mut Self {
+        if value.is_empty() {
+            self.set_null(key)
+        } else {
+            self.insert(key, AttributeValue::Ns(value.iter().map(ToString::to_string).collect()))
+        }
     }
 }
 
-pub async fn insert_item(table: &str, item: HashMap<String, AttributeValue>) -> Result<bool, FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_dynamodb::Client::new(&config);
+// Maps an AWS error code, or a transaction cancellation reason, to the matching error
+pub(crate) fn error_from_code(code: Option<&str>, description: &str) -> FluffError {
+    match code {
+        Some("ConditionalCheckFailedException") | Some("ConditionalCheckFailed") => FluffError::new_u16(
+            409,
+            "ConditionalCheckFailed",
+            "The condition on the database item was not met",
+            false,
+        ),
+        Some("TransactionConflictException") | Some("TransactionConflict") => FluffError::new_u16(
+            409,
+            "TransactionConflict",
+            "Another transaction is in progress on the database item",
+            true,
+        ),
+        Some("ThrottlingException")
+        | Some("ThrottlingError")
+        | Some("ProvisionedThroughputExceededException")
+        | Some("ProvisionedThroughputExceeded")
+        | Some("RequestLimitExceeded") => FluffError::new_u16(
+            429,
+            "DatabaseThrottled",
+            "Too many requests on the database",
+            true,
+        ),
+        Some("ValidationException")
+        | Some("ValidationError")
+        | Some("SerializationException")
+        | Some("ItemCollectionSizeLimitExceededException")
+        | Some("ItemCollectionSizeLimitExceeded") => FluffError::new_u16(
+            400,
+            "DatabaseValidationError",
+            "The database rejected the request",
+            false,
+        ),
+        Some("AccessDeniedException")
+        | Some("UnrecognizedClientException")
+        | Some("MissingAuthenticationTokenException") => FluffError::new_u16(
+            403,
+            "DatabaseAccessDenied",
+            "Access to the database was denied",
+            false,
+        ),
+        Some("ResourceNotFoundException") => FluffError::new_u16(
+            500,
+            "DatabaseTableNotFound",
+            "The database table does not exist",
+            false,
+        ),
+        _ => FluffError::new_u16(500, "DatabaseError", description, true),
+    }
+}
+
+pub(crate) fn database_error<E, R>(table: &str, description: &str, err: SdkError<E, R>) -> FluffError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+    R: std::fmt::Debug,
+{
+    let error = error_from_code(err.code(), description).add_context(table);
+    match err.message() {
+        Some(message) => error.add_context(message),
+        None => error.add_context(&DisplayErrorContext(&err).to_string()),
+    }
+}
+
+pub(crate) fn invalid_request(err: impl std::error::Error) -> FluffError {
+    FluffError::new_u16(400, "InvalidRequest", "Unable to build the database request", false)
+        .add_context(&err.to_string())
+}
+
+pub async fn insert_item(table: &str, item: impl Into<HashMap<String, AttributeValue>>) -> Result<bool, FluffError> {
+    insert_item_with_condition(table, item, &Condition::new()).await
+}
+
+pub async fn insert_item_with_condition(
+    table: &str,
+    item: impl Into<HashMap<String, AttributeValue>>,
+    condition: &Condition,
+) -> Result<bool, FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    let mut attributes = ExpressionAttributes::default();
+    let condition_expression = condition.render(&mut attributes);
+    let (names, values) = attributes.into_parts();
 
     client.put_item()
         .table_name(table)
-        .set_item(Some(item))
+        .set_item(Some(item.into()))
+        .set_condition_expression(condition_expression)
+        .set_expression_attribute_names(names)
+        .set_expression_attribute_values(values)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(
-                500,
-                "DatabaseError",
-                "Failed to insert item in database",
-                true,
-            )
-            .add_context(table)
-            .add_context(&err.to_string())
-        })?;
+        .map_err(|err| database_error(table, "Failed to insert item in database", err))?;
 
     Ok(true)
 }
 
+// Fails with a 404 ItemNotFound when no item matches the keys
 pub async fn get_item(table: &str, keys: HashMap<String, AttributeValue>, consistent: bool) -> Result<DynamoItem, FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_dynamodb::Client::new(&config);
+    get_item_opt(table, keys, consistent).await?.ok_or_else(|| {
+        FluffError::new_u16(
+            404,
+            "ItemNotFound",
+            "Item not found in database or expired",
+            false,
+        )
+        .add_context(table)
+    })
+}
+
+pub async fn get_item_opt(table: &str, keys: HashMap<String, AttributeValue>, consistent: bool) -> Result<Option<DynamoItem>, FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
 
     let output = client
         .get_item()
@@ -66,22 +413,173 @@ pub async fn get_item(table: &str, keys: HashMap<String, AttributeValue>, consis
         .consistent_read(consistent)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(
-                500,
-                "DatabaseError",
-                "Failed to get item in database",
-                true,
-            )
-            .add_context(table)
-            .add_context(&err.to_string())
-        })?;
-
-    output.item.ok_or(FluffError::new_u16(
-        500,
-        "DatabaseError",
-        "Item not found in database or expired",
-        true,
-    ))
-    .map(|item| DynamoItem { data: item })
+        .map_err(|err| database_error(table, "Failed to get item in database", err))?;
+
+    Ok(output.item.map(|item| DynamoItem { data: item }))
+}
+
+// Applies the update and returns the item as stored after the update, an empty update is rejected
+pub async fn update_item(
+    table: &str,
+    keys: HashMap<String, AttributeValue>,
+    update: &Update,
+    condition: &Condition,
+) -> Result<DynamoItem, FluffError> {
+    update.validate().map_err(|err| err.add_context(table))?;
+    let client = FluffContext::shared().await.dynamodb();
+
+    let mut attributes = ExpressionAttributes::default();
+    let update_expression = update.render(&mut attributes);
+    let condition_expression = condition.render(&mut attributes);
+    let (names, values) = attributes.into_parts();
+
+    let output = client
+        .update_item()
+        .table_name(table)
+        .set_key(Some(keys))
+        .set_update_expression(update_expression)
+        .set_condition_expression(condition_expression)
+        .set_expression_attribute_names(names)
+        .set_expression_attribute_values(values)
+        .return_values(ReturnValue::AllNew)
+        .send()
+        .await
+        .map_err(|err| database_error(table, "Failed to update item in database", err))?;
+
+    Ok(DynamoItem::from(output.attributes.unwrap_or_default()))
+}
+
+// Returns the deleted item when `return_old` is set and the item existed
+pub async fn delete_item(
+    table: &str,
+    keys: HashMap<String, AttributeValue>,
+    condition: &Condition,
+    return_old: bool,
+) -> Result<Option<DynamoItem>, FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    let mut attributes = ExpressionAttributes::default();
+    let condition_expression = condition.render(&mut attributes);
+    let (names, values) = attributes.into_parts();
+
+    let output = client
+        .delete_item()
+        .table_name(table)
+        .set_key(Some(keys))
+        .set_condition_expression(condition_expression)
+        .set_expression_attribute_names(names)
+        .set_expression_attribute_values(values)
+        .return_values(if return_old { ReturnValue::AllOld } else { ReturnValue::None })
+        .send()
+        .await
+        .map_err(|err| database_error(table, "Failed to delete item in database", err))?;
+
+    Ok(output.attributes.filter(|item| !item.is_empty()).map(DynamoItem::from))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn getters_read_what_setters_write() {
+        let mut nested = DynamoItem::new();
+        nested.set_string("city", "Lyon");
+        let mut item = DynamoItem::new();
+        item.set_string("name", "fluff")
+            .set_strings_vec("tags", vec![String::from("a"), String::from("b")])
+            .set_i64("delta", -3)
+            .set_u64("count", 7)
+            .set_bool("active", true)
+            .set_map("address", nested)
+            .set_list("history", vec![AttributeValue::N(String::from("1"))])
+            .set_bytes("avatar", &[1, 2, 3])
+            .set_number_set("scores", &[10, 20]);
+        item.set_f64("ratio", 0.5).unwrap();
+
+        assert_eq!(item.get_string("name").unwrap(), "fluff");
+        assert_eq!(item.get_strings_vec("tags").unwrap(), vec!["a", "b"]);
+        assert_eq!(item.get_i64("delta").unwrap(), -3);
+        assert_eq!(item.get_u64("count").unwrap(), 7);
+        assert_eq!(item.get_f64("ratio").unwrap(), 0.5);
+        assert!(item.get_bool("active").unwrap());
+        assert_eq!(item.get_map("address").unwrap().get_string("city").unwrap(), "Lyon");
+        assert_eq!(item.get_list("history").unwrap().len(), 1);
+        assert_eq!(item.get_bytes("avatar").unwrap(), vec![1, 2, 3]);
+        assert_eq!(item.get_number_set::<u32>("scores").unwrap(), vec![10, 20]);
+    }
+
+    #[test]
+    fn empty_sets_are_stored_as_null() {
+        let mut item = DynamoItem::new();
+        item.set_strings_vec("tags", vec![]).set_number_set::<u32>("scores", &[]);
+
+        assert_eq!(item.get("tags"), Some(&AttributeValue::Null(true)));
+        assert_eq!(item.get("scores"), Some(&AttributeValue::Null(true)));
+        assert!(item.get_strings_vec("tags").unwrap().is_empty());
+        assert_eq!(item.get_strings_vec_opt("tags"), Some(vec![]));
+        assert!(item.get_number_set::<u32>("scores").unwrap().is_empty());
+    }
+
+    #[test]
+    fn failures_name_the_attribute() {
+        let mut item = DynamoItem::new();
+        item.set_u64("count", 7).set_i64("delta", -3);
+
+        for err in [
+            item.get_string("missing").unwrap_err(),
+            item.get_string("count").unwrap_err(),
+            item.get_strings_vec("count").unwrap_err(),
+            item.get_u64("delta").unwrap_err(),
+            item.get_bool("count").unwrap_err(),
+        ] {
+            assert_eq!(err.error_name, "DatabaseError");
+            assert_eq!(err.context.len(), 1, "{:?}", err);
+        }
+        assert_eq!(item.get_string("missing").unwrap_err().context, vec![String::from("missing")]);
+        assert_eq!(item.get_u64("delta").unwrap_err().error_description, "Value is not an unsigned 64-bit integer");
+        assert_eq!(item.get_string_opt("count"), None);
+    }
+
+    #[test]
+    fn set_f64_rejects_non_finite_numbers() {
+        let mut item = DynamoItem::new();
+
+        for value in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e130] {
+            let err = item.set_f64("ratio", value).unwrap_err();
+            assert!(err.context.contains(&String::from("ratio")), "{}: {:?}", value, err);
+        }
+        assert!(!item.contains_key("ratio"));
+    }
+
+    #[test]
+    fn error_codes_are_classified() {
+        for (code, http_code, error_name, can_retry) in [
+            ("ConditionalCheckFailedException", 409, "ConditionalCheckFailed", false),
+            ("ConditionalCheckFailed", 409, "ConditionalCheckFailed", false),
+            ("TransactionConflictException", 409, "TransactionConflict", true),
+            ("TransactionConflict", 409, "TransactionConflict", true),
+            ("ThrottlingException", 429, "DatabaseThrottled", true),
+            ("ProvisionedThroughputExceededException", 429, "DatabaseThrottled", true),
+            ("ProvisionedThroughputExceeded", 429, "DatabaseThrottled", true),
+            ("RequestLimitExceeded", 429, "DatabaseThrottled", true),
+            ("ValidationException", 400, "DatabaseValidationError", false),
+            ("ItemCollectionSizeLimitExceeded", 400, "DatabaseValidationError", false),
+            ("AccessDeniedException", 403, "DatabaseAccessDenied", false),
+            ("UnrecognizedClientException", 403, "DatabaseAccessDenied", false),
+            ("ResourceNotFoundException", 500, "DatabaseTableNotFound", false),
+        ] {
+            let err = error_from_code(Some(code), "Failed");
+            assert_eq!((err.http_code, err.error_name.as_str(), err.can_retry), (http_code, error_name, can_retry), "{}", code);
+        }
+    }
+
+    #[test]
+    fn unknown_codes_are_retryable_database_errors() {
+        for code in [None, Some("InternalServerError"), Some("SomethingNew")] {
+            let err = error_from_code(code, "Failed to read item");
+            assert_eq!((err.http_code, err.error_name.as_str(), err.can_retry), (500, "DatabaseError", true));
+            assert_eq!(err.error_description, "Failed to read item");
+        }
+    }
 }
@@ -0,0 +1,273 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::hash::{BuildHasher, Hasher};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_sdk_dynamodb::types::{AttributeValue, DeleteRequest, KeysAndAttributes, PutRequest, WriteRequest};
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::dynamodb::{database_error, invalid_request, DynamoItem, PrimaryKey};
+
+const BATCH_GET_LIMIT: usize = 100;
+const BATCH_WRITE_LIMIT: usize = 25;
+const MAX_ATTEMPTS: u32 = 8;
+
+#[derive(Debug, Clone)]
+pub enum BatchWrite {
+    Put(HashMap<String, AttributeValue>),
+    Delete(HashMap<String, AttributeValue>),
+}
+
+impl BatchWrite {
+    fn key(&self, key_names: &[&str]) -> Result<PrimaryKey, FluffError> {
+        match self {
+            BatchWrite::Put(item) => PrimaryKey::from_item(key_names, item),
+            BatchWrite::Delete(key) => PrimaryKey::from_item(key_names, key),
+        }
+        .map_err(|err| {
+            FluffError::new_u16(400, "InvalidKey", "Batch write is missing a key attribute", false)
+                .add_context(&err.context.join(", "))
+        })
+    }
+
+    fn into_request(self) -> Result<WriteRequest, FluffError> {
+        let request = match self {
+            BatchWrite::Put(item) => WriteRequest::builder()
+                .put_request(PutRequest::builder().set_item(Some(item)).build().map_err(invalid_request)?)
+                .build(),
+            BatchWrite::Delete(key) => WriteRequest::builder()
+                .delete_request(DeleteRequest::builder().set_key(Some(key)).build().map_err(invalid_request)?)
+                .build(),
+        };
+        Ok(request)
+    }
+}
+
+fn unprocessed(table: &str, count: usize) -> FluffError {
+    FluffError::new_u16(
+        503,
+        "DatabaseError",
+        "Some items were left unprocessed by the database",
+        true,
+    )
+    .add_context(table)
+    .add_context(&format!("{} unprocessed items", count))
+}
+
+// Exponential backoff starting at 50ms and capped at 5s, with full jitter so that concurrent
+// Lambdas do not retry in lockstep
+async fn backoff(attempt: u32) {
+    let delay = 50u64.saturating_mul(1 << attempt.min(10)).min(5_000);
+    tokio::time::sleep(Duration::from_millis(random_below(delay + 1))).await;
+}
+
+// RandomState is seeded randomly for every instance, which is enough for jitter
+fn random_below(bound: u64) -> u64 {
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u128(SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos());
+    hasher.finish() % bound
+}
+
+// Normalized key of each fetched item, with the keys of the caller it answers
+type RequestedKeys = HashMap<PrimaryKey, Vec<PrimaryKey>>;
+
+// The keys to fetch, once each and by chunks of 100, and for each of them the keys the caller wrote,
+// which differ when a number is written differently
+fn get_chunks(
+    keys: &[HashMap<String, AttributeValue>],
+) -> Result<(Vec<Vec<PrimaryKey>>, RequestedKeys), FluffError> {
+    let mut unique_keys = Vec::with_capacity(keys.len());
+    let mut requested = RequestedKeys::new();
+    for key in keys {
+        let key = PrimaryKey::try_from(key)?;
+        let spellings = requested.entry(key.normalized()).or_default();
+        if spellings.is_empty() {
+            unique_keys.push(key.clone());
+        }
+        if !spellings.contains(&key) {
+            spellings.push(key);
+        }
+    }
+    let chunks = unique_keys.chunks(BATCH_GET_LIMIT).map(<[PrimaryKey]>::to_vec).collect();
+    Ok((chunks, requested))
+}
+
+// Duplicated keys are only fetched once, missing items are absent from the result.
+// Items are returned under the keys of `keys`
+pub async fn batch_get_items(
+    table: &str,
+    keys: Vec<HashMap<String, AttributeValue>>,
+    consistent: bool,
+) -> Result<HashMap<PrimaryKey, DynamoItem>, FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    let (chunks, requested) = get_chunks(&keys)?;
+    let key_names = match chunks.first().and_then(|chunk| chunk.first()) {
+        Some(key) => key.names().into_iter().map(String::from).collect::<Vec<String>>(),
+        None => return Ok(HashMap::new()),
+    };
+    let key_names: Vec<&str> = key_names.iter().map(String::as_str).collect();
+
+    let mut results = HashMap::new();
+    for chunk in chunks {
+        let mut pending: Vec<HashMap<String, AttributeValue>> = chunk.iter().map(PrimaryKey::to_attributes).collect();
+        let mut attempt = 0;
+
+        while !pending.is_empty() {
+            if attempt >= MAX_ATTEMPTS {
+                return Err(unprocessed(table, pending.len()));
+            }
+            if attempt > 0 {
+                backoff(attempt).await;
+            }
+            attempt += 1;
+
+            let request = KeysAndAttributes::builder()
+                .set_keys(Some(pending))
+                .consistent_read(consistent)
+                .build()
+                .map_err(invalid_request)?;
+            let output = client
+                .batch_get_item()
+                .request_items(table, request)
+                .send()
+                .await
+                .map_err(|err| database_error(table, "Failed to get items in database", err))?;
+
+            for item in output.responses.and_then(|mut responses| responses.remove(table)).unwrap_or_default() {
+                let returned = PrimaryKey::from_item(&key_names, &item)?.normalized();
+                for key in requested.get(&returned).into_iter().flatten() {
+                    results.insert(key.clone(), DynamoItem::from(item.clone()));
+                }
+            }
+            pending = output
+                .unprocessed_keys
+                .and_then(|mut unprocessed| unprocessed.remove(table))
+                .map(|unprocessed| unprocessed.keys)
+                .unwrap_or_default();
+        }
+    }
+
+    Ok(results)
+}
+
+// The last write of each key, in the order of `writes`, by chunks of 25
+fn write_chunks(key_names: &[&str], writes: Vec<BatchWrite>) -> Result<Vec<Vec<WriteRequest>>, FluffError> {
+    let keys = writes
+        .iter()
+        .map(|write| write.key(key_names).map(|key| key.normalized()))
+        .collect::<Result<Vec<PrimaryKey>, FluffError>>()?;
+    let last_writes: HashMap<&PrimaryKey, usize> = keys.iter().enumerate().map(|(i, key)| (key, i)).collect();
+    let requests = writes
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| last_writes.get(&keys[*i]) == Some(i))
+        .map(|(_, write)| write.into_request())
+        .collect::<Result<Vec<WriteRequest>, FluffError>>()?;
+    Ok(requests.chunks(BATCH_WRITE_LIMIT).map(<[WriteRequest]>::to_vec).collect())
+}
+
+// `key_names` are the key attributes of the table. DynamoDB rejects a batch touching the same
+// item twice, so only the last write of each key is sent
+pub async fn batch_write_items(table: &str, key_names: &[&str], writes: Vec<BatchWrite>) -> Result<(), FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    for mut pending in write_chunks(key_names, writes)? {
+        let mut attempt = 0;
+
+        while !pending.is_empty() {
+            if attempt >= MAX_ATTEMPTS {
+                return Err(unprocessed(table, pending.len()));
+            }
+            if attempt > 0 {
+                backoff(attempt).await;
+            }
+            attempt += 1;
+
+            let output = client
+                .batch_write_item()
+                .request_items(table, pending)
+                .send()
+                .await
+                .map_err(|err| database_error(table, "Failed to write items in database", err))?;
+
+            pending = output
+                .unprocessed_items
+                .and_then(|mut unprocessed| unprocessed.remove(table))
+                .unwrap_or_default();
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(id: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([(String::from("id"), AttributeValue::N(String::from(id)))])
+    }
+
+    fn put(id: &str, value: &str) -> BatchWrite {
+        let mut item = key(id);
+        item.insert(String::from("value"), AttributeValue::S(String::from(value)));
+        BatchWrite::Put(item)
+    }
+
+    #[test]
+    fn gets_are_deduplicated_and_chunked_by_100() {
+        let keys: Vec<_> = (0..250).chain(0..50).map(|id| key(&id.to_string())).collect();
+        let (chunks, requested) = get_chunks(&keys).unwrap();
+
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), vec![100, 100, 50]);
+        assert_eq!(requested.len(), 250);
+    }
+
+    #[test]
+    fn numbers_written_differently_are_fetched_once() {
+        let (chunks, requested) = get_chunks(&[key("1.0"), key("1"), key("1.0")]).unwrap();
+
+        assert_eq!(chunks, vec![vec![PrimaryKey::try_from(&key("1.0")).unwrap()]]);
+        let returned = PrimaryKey::try_from(&key("1")).unwrap().normalized();
+        assert_eq!(
+            requested[&returned],
+            vec![PrimaryKey::try_from(&key("1.0")).unwrap(), PrimaryKey::try_from(&key("1")).unwrap()]
+        );
+    }
+
+    #[test]
+    fn writes_keep_the_last_write_of_each_key() {
+        let writes = vec![put("1", "first"), put("2", "only"), BatchWrite::Delete(key("1.0")), put("1", "last")];
+        let chunks = write_chunks(&["id"], writes).unwrap();
+
+        let values: Vec<_> = chunks[0]
+            .iter()
+            .map(|request| {
+                let item = request.put_request().map(|put| &put.item);
+                item.and_then(|item| item.get("value")).cloned()
+            })
+            .collect();
+        assert_eq!(
+            values,
+            vec![Some(AttributeValue::S(String::from("only"))), Some(AttributeValue::S(String::from("last")))]
+        );
+    }
+
+    #[test]
+    fn writes_are_chunked_by_25() {
+        let writes = (0..60).map(|id| put(&id.to_string(), "v")).collect();
+        let chunks = write_chunks(&["id"], writes).unwrap();
+
+        assert_eq!(chunks.iter().map(Vec::len).collect::<Vec<_>>(), vec![25, 25, 10]);
+    }
+
+    #[test]
+    fn writes_without_key_are_rejected() {
+        let err = write_chunks(&["id", "round"], vec![put("1", "v")]).unwrap_err();
+
+        assert_eq!(err.http_code, 400);
+        assert_eq!(err.error_name, "InvalidKey");
+    }
+}
@@ -0,0 +1,230 @@
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::FluffError;
+use crate::services::aws::dynamodb::{self, serialization, Condition, DynamoItem};
+use crate::config::FluffConfig;
+
+// Epoch seconds after which DynamoDB may reap the item, tables must enable TTL on it
+pub const TTL_ATTRIBUTE: &str = "expires_at";
+
+fn now_as_sec() -> Result<u64, FluffError> {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|now| now.as_secs())
+        .map_err(|err| {
+            FluffError::new_u16(
+                500,
+                "SystemTimeError",
+                "Current system time is before UNIX EPOCH",
+                true,
+            )
+            .add_context(&err.to_string())
+        })
+}
+
+pub fn expiry_from_now(ttl: Duration) -> Result<u64, FluffError> {
+    Ok(now_as_sec()? + ttl.as_secs())
+}
+
+pub fn stamp_expiry(item: &mut HashMap<String, AttributeValue>, ttl: Duration) -> Result<(), FluffError> {
+    item.insert(String::from(TTL_ATTRIBUTE), AttributeValue::N(expiry_from_now(ttl)?.to_string()));
+    Ok(())
+}
+
+// DynamoDB only reaps expired items eventually, so readers of tables with TTL enabled must skip
+// them themselves, see `ExpiringStore` and `ReadRequest::skip_expired`
+pub fn is_expired(item: &HashMap<String, AttributeValue>) -> bool {
+    let expires_at = item
+        .get(TTL_ATTRIBUTE)
+        .and_then(|value| value.as_n().ok())
+        .and_then(|value| value.parse::<u64>().ok());
+    match (expires_at, now_as_sec()) {
+        (Some(expires_at), Ok(now)) => expires_at <= now,
+        _ => false,
+    }
+}
+
+pub async fn insert_item_with_ttl(
+    table: &str,
+    item: impl Into<HashMap<String, AttributeValue>>,
+    ttl: Duration,
+) -> Result<bool, FluffError> {
+    let mut item = item.into();
+    stamp_expiry(&mut item, ttl)?;
+
+    dynamodb::insert_item(table, item).await
+}
+
+// Passes when there is no record with the key, or when it expired but was not reaped yet
+fn new_record_condition() -> Result<Condition, FluffError> {
+    Ok(Condition::new().expression(
+        "attribute_not_exists(#id) OR #expires_at <= :now",
+        HashMap::from([
+            (String::from("#id"), String::from("id")),
+            (String::from("#expires_at"), String::from(TTL_ATTRIBUTE)),
+        ]),
+        HashMap::from([(String::from(":now"), AttributeValue::N(now_as_sec()?.to_string()))]),
+    ))
+}
+
+// Short-lived records (OAuth states, one-time codes, sessions) sharing the ephemeral table.
+// Keys are prefixed with the namespace so that several stores can live in the same table.
+pub struct ExpiringStore<T> {
+    table: Option<String>,
+    namespace: String,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ExpiringStore<T>
+where
+    T: Serialize + DeserializeOwned,
+{
+    pub fn new(namespace: &str) -> ExpiringStore<T> {
+        ExpiringStore {
+            table: None,
+            namespace: String::from(namespace),
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn with_table(mut self, table: &str) -> Self {
+        self.table = Some(String::from(table));
+        self
+    }
+
+    // Defaults to the ephemeral table of the current stage
+    fn table(&self) -> Result<&str, FluffError> {
+        match &self.table {
+            Some(table) => Ok(table),
+            None => Ok(&FluffConfig::shared()?.table_ephemeral),
+        }
+    }
+
+    fn keys(&self, key: &str) -> HashMap<String, AttributeValue> {
+        HashMap::from([(
+            String::from("id"),
+            AttributeValue::S(format!("{}#{}", self.namespace, key)),
+        )])
+    }
+
+    fn item(&self, key: &str, value: &T, ttl: Duration) -> Result<HashMap<String, AttributeValue>, FluffError> {
+        let mut item = self.keys(key);
+        item.insert(String::from("data"), serialization::to_attribute_value(value)?);
+        stamp_expiry(&mut item, ttl)?;
+        Ok(item)
+    }
+
+    // None when there is no record or when it expired but was not reaped yet
+    fn data(item: Option<HashMap<String, AttributeValue>>) -> Result<Option<T>, FluffError> {
+        let item = match item {
+            Some(item) if !is_expired(&item) => item,
+            _ => return Ok(None),
+        };
+        let data = item.get("data").cloned().ok_or_else(|| {
+            FluffError::new_u16(500, "DatabaseError", "Key not found in database item", true)
+                .add_context("data")
+        })?;
+        serialization::from_attribute_value(data).map(Some)
+    }
+
+    pub async fn put(&self, key: &str, value: &T, ttl: Duration) -> Result<(), FluffError> {
+        dynamodb::insert_item(self.table()?, self.item(key, value, ttl)?).await?;
+        Ok(())
+    }
+
+    // Fails with a 409 ConditionalCheckFailed if a record with this key has not expired yet
+    pub async fn put_new(&self, key: &str, value: &T, ttl: Duration) -> Result<(), FluffError> {
+        let condition = new_record_condition()?;
+
+        dynamodb::insert_item_with_condition(self.table()?, self.item(key, value, ttl)?, &condition).await?;
+        Ok(())
+    }
+
+    pub async fn get(&self, key: &str) -> Result<Option<T>, FluffError> {
+        let item = dynamodb::get_item_opt(self.table()?, self.keys(key), true).await?;
+        Self::data(item.map(DynamoItem::into_hashmap))
+    }
+
+    // Reads and deletes the record at once, so that a one-time code cannot be used twice
+    pub async fn take(&self, key: &str) -> Result<Option<T>, FluffError> {
+        let item = dynamodb::delete_item(self.table()?, self.keys(key), &Condition::new(), true).await?;
+        Self::data(item.map(DynamoItem::into_hashmap))
+    }
+
+    pub async fn delete(&self, key: &str) -> Result<(), FluffError> {
+        dynamodb::delete_item(self.table()?, self.keys(key), &Condition::new(), false).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+    use crate::services::aws::dynamodb::expression::ExpressionAttributes;
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Code {
+        value: String,
+    }
+
+    fn store() -> ExpiringStore<Code> {
+        ExpiringStore::new("codes").with_table("ephemeral")
+    }
+
+    fn record(expires_at: u64) -> HashMap<String, AttributeValue> {
+        let mut item = store().keys("abc");
+        item.insert(String::from("data"), serialization::to_attribute_value(&Code { value: String::from("x") }).unwrap());
+        item.insert(String::from(TTL_ATTRIBUTE), AttributeValue::N(expires_at.to_string()));
+        item
+    }
+
+    #[test]
+    fn expiry_is_stamped_from_now() {
+        let item = store().item("abc", &Code { value: String::from("x") }, Duration::from_secs(60)).unwrap();
+        let expires_at: u64 = item[TTL_ATTRIBUTE].as_n().unwrap().parse().unwrap();
+
+        assert!(expires_at >= now_as_sec().unwrap() + 59);
+        assert_eq!(item["id"], AttributeValue::S(String::from("codes#abc")));
+        assert!(!is_expired(&item));
+    }
+
+    #[test]
+    fn items_expire_at_their_timestamp() {
+        let now = now_as_sec().unwrap();
+
+        assert!(is_expired(&record(now)));
+        assert!(is_expired(&record(1)));
+        assert!(!is_expired(&record(now + 60)));
+        assert!(!is_expired(&store().keys("abc")));
+    }
+
+    #[test]
+    fn expired_records_are_not_returned() {
+        let now = now_as_sec().unwrap();
+
+        assert_eq!(ExpiringStore::<Code>::data(Some(record(now + 60))).unwrap(), Some(Code { value: String::from("x") }));
+        assert_eq!(ExpiringStore::<Code>::data(Some(record(now))).unwrap(), None);
+        assert_eq!(ExpiringStore::<Code>::data(None).unwrap(), None);
+    }
+
+    #[test]
+    fn expired_records_can_be_replaced() {
+        let before = now_as_sec().unwrap();
+        let mut attributes = ExpressionAttributes::default();
+        let expression = new_record_condition().unwrap().render(&mut attributes);
+        let (names, values) = attributes.into_parts();
+        let now: u64 = values.unwrap()[":now"].as_n().unwrap().parse().unwrap();
+
+        assert_eq!(expression.as_deref(), Some("(attribute_not_exists(#id) OR #expires_at <= :now)"));
+        assert_eq!(names.unwrap()["#expires_at"], TTL_ATTRIBUTE);
+        assert!(now >= before && now <= now_as_sec().unwrap());
+    }
+}
@@ -0,0 +1,475 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::errors::FluffError;
+
+pub(crate) type ExpressionNames = Option<HashMap<String, String>>;
+pub(crate) type ExpressionValues = Option<HashMap<String, AttributeValue>>;
+
+// Collects the placeholders of one request so that several expressions can share them
+#[derive(Debug, Default)]
+pub(crate) struct ExpressionAttributes {
+    names: HashMap<String, String>,
+    values: HashMap<String, AttributeValue>,
+}
+
+impl ExpressionAttributes {
+    // Each segment of the path gets its own placeholder
+    pub(crate) fn name(&mut self, attribute: &AttributePath) -> String {
+        attribute
+            .segments
+            .iter()
+            .map(|segment| {
+                let existing = self
+                    .names
+                    .iter()
+                    .find(|(_, name)| name == &segment)
+                    .map(|(placeholder, _)| placeholder.clone());
+                existing.unwrap_or_else(|| {
+                    let placeholder = format!("#fluff_n{}", self.names.len());
+                    self.names.insert(placeholder.clone(), segment.clone());
+                    placeholder
+                })
+            })
+            .collect::<Vec<String>>()
+            .join(".")
+    }
+
+    pub(crate) fn value(&mut self, value: AttributeValue) -> String {
+        let placeholder = format!(":fluff_v{}", self.values.len());
+        self.values.insert(placeholder.clone(), value);
+        placeholder
+    }
+
+    pub(crate) fn extend(&mut self, names: &HashMap<String, String>, values: &HashMap<String, AttributeValue>) {
+        self.names.extend(names.clone());
+        self.values.extend(values.clone());
+    }
+
+    pub(crate) fn into_parts(self) -> (ExpressionNames, ExpressionValues) {
+        (
+            Some(self.names).filter(|names| !names.is_empty()),
+            Some(self.values).filter(|values| !values.is_empty()),
+        )
+    }
+}
+
+// Path to an attribute, possibly nested in maps. Converting from a string splits it on dots
+// (`profile.name`), use `AttributePath::top_level` for a name which contains dots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributePath {
+    segments: Vec<String>,
+}
+
+impl AttributePath {
+    pub fn top_level(name: &str) -> AttributePath {
+        AttributePath {
+            segments: vec![String::from(name)],
+        }
+    }
+
+    // Nested attribute, `child` is never split on dots
+    pub fn child(mut self, name: &str) -> Self {
+        self.segments.push(String::from(name));
+        self
+    }
+}
+
+impl std::fmt::Display for AttributePath {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.segments.join("."))
+    }
+}
+
+impl From<&str> for AttributePath {
+    fn from(path: &str) -> Self {
+        AttributePath {
+            segments: path.split('.').map(String::from).collect(),
+        }
+    }
+}
+
+impl From<String> for AttributePath {
+    fn from(path: String) -> Self {
+        AttributePath::from(path.as_str())
+    }
+}
+
+impl From<&AttributePath> for AttributePath {
+    fn from(path: &AttributePath) -> Self {
+        path.clone()
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Clause {
+    AttributeExists(AttributePath),
+    AttributeNotExists(AttributePath),
+    Compare(AttributePath, &'static str, AttributeValue),
+    Raw {
+        expression: String,
+        names: HashMap<String, String>,
+        values: HashMap<String, AttributeValue>,
+    },
+}
+
+// A DynamoDB condition expression, all clauses must hold for the write to happen
+#[derive(Debug, Clone, Default)]
+pub struct Condition {
+    clauses: Vec<Clause>,
+}
+
+impl Condition {
+    pub fn new() -> Condition {
+        Condition::default()
+    }
+
+    pub fn attribute_exists(mut self, attribute: impl Into<AttributePath>) -> Self {
+        self.clauses.push(Clause::AttributeExists(attribute.into()));
+        self
+    }
+
+    pub fn attribute_not_exists(mut self, attribute: impl Into<AttributePath>) -> Self {
+        self.clauses.push(Clause::AttributeNotExists(attribute.into()));
+        self
+    }
+
+    pub fn equals(self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.compare(attribute, "=", value)
+    }
+
+    pub fn not_equals(self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.compare(attribute, "<>", value)
+    }
+
+    pub fn less_than(self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.compare(attribute, "<", value)
+    }
+
+    pub fn greater_than(self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.compare(attribute, ">", value)
+    }
+
+    fn compare(mut self, attribute: impl Into<AttributePath>, operator: &'static str, value: AttributeValue) -> Self {
+        self.clauses.push(Clause::Compare(attribute.into(), operator, value));
+        self
+    }
+
+    // Raw expression, its placeholders must not start with `#fluff_` or `:fluff_`
+    pub fn expression(
+        mut self,
+        expression: &str,
+        names: HashMap<String, String>,
+        values: HashMap<String, AttributeValue>,
+    ) -> Self {
+        self.clauses.push(Clause::Raw {
+            expression: String::from(expression),
+            names,
+            values,
+        });
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.clauses.is_empty()
+    }
+
+    // Evaluates the condition against the current item, raw expressions are not supported
+    pub(crate) fn evaluate(&self, item: Option<&HashMap<String, AttributeValue>>) -> Result<bool, FluffError> {
+        for clause in &self.clauses {
+            let value = |attribute: &AttributePath| item.and_then(|item| lookup(item, attribute));
+            let holds = match clause {
+                Clause::AttributeExists(attribute) => value(attribute).is_some(),
+                Clause::AttributeNotExists(attribute) => value(attribute).is_none(),
+                Clause::Compare(attribute, operator, expected) => match (value(attribute), *operator) {
+                    (None, _) => false,
+                    (Some(current), "=") => current == expected,
+                    (Some(current), "<>") => current != expected,
+                    (Some(current), "<") => compare_values(current, expected) == Some(Ordering::Less),
+                    (Some(current), _) => compare_values(current, expected) == Some(Ordering::Greater),
+                },
+                Clause::Raw { expression, .. } => {
+                    return Err(FluffError::new_u16(
+                        400,
+                        "UnsupportedCondition",
+                        "Raw condition expressions can only be evaluated by DynamoDB",
+                        false,
+                    )
+                    .add_context(expression))
+                }
+            };
+            if !holds {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    pub(crate) fn render(&self, attributes: &mut ExpressionAttributes) -> Option<String> {
+        if self.clauses.is_empty() {
+            return None;
+        }
+
+        let clauses: Vec<String> = self
+            .clauses
+            .iter()
+            .map(|clause| match clause {
+                Clause::AttributeExists(attribute) => {
+                    format!("attribute_exists({})", attributes.name(attribute))
+                }
+                Clause::AttributeNotExists(attribute) => {
+                    format!("attribute_not_exists({})", attributes.name(attribute))
+                }
+                Clause::Compare(attribute, operator, value) => format!(
+                    "{} {} {}",
+                    attributes.name(attribute),
+                    operator,
+                    attributes.value(value.clone())
+                ),
+                Clause::Raw {
+                    expression,
+                    names,
+                    values,
+                } => {
+                    attributes.extend(names, values);
+                    format!("({})", expression)
+                }
+            })
+            .collect();
+        Some(clauses.join(" AND "))
+    }
+}
+
+// Looks up an attribute path in an item, used to evaluate expressions outside of DynamoDB
+pub(crate) fn lookup<'a>(item: &'a HashMap<String, AttributeValue>, attribute: &AttributePath) -> Option<&'a AttributeValue> {
+    let mut segments = attribute.segments.iter();
+    let mut value = item.get(segments.next()?)?;
+    for segment in segments {
+        value = value.as_m().ok()?.get(segment)?;
+    }
+    Some(value)
+}
+
+fn is_empty_set(value: &AttributeValue) -> bool {
+    match value {
+        AttributeValue::Ss(values) | AttributeValue::Ns(values) => values.is_empty(),
+        AttributeValue::Bs(values) => values.is_empty(),
+        _ => false,
+    }
+}
+
+// Orders two values the way DynamoDB does, values of different types cannot be ordered
+pub(crate) fn compare_values(left: &AttributeValue, right: &AttributeValue) -> Option<Ordering> {
+    match (left, right) {
+        (AttributeValue::N(left), AttributeValue::N(right)) => {
+            let left: f64 = left.parse().ok()?;
+            let right: f64 = right.parse().ok()?;
+            left.partial_cmp(&right)
+        }
+        (AttributeValue::S(left), AttributeValue::S(right)) => Some(left.cmp(right)),
+        (AttributeValue::B(left), AttributeValue::B(right)) => Some(left.as_ref().cmp(right.as_ref())),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Clone)]
+enum SetAction {
+    Value(AttributePath, AttributeValue),
+    IfNotExists(AttributePath, AttributeValue),
+    AppendToList(AttributePath, Vec<AttributeValue>),
+}
+
+// A DynamoDB update expression made of SET, REMOVE, ADD and DELETE actions
+#[derive(Debug, Clone, Default)]
+pub struct Update {
+    set: Vec<SetAction>,
+    remove: Vec<AttributePath>,
+    add: Vec<(AttributePath, AttributeValue)>,
+    delete: Vec<(AttributePath, AttributeValue)>,
+}
+
+impl Update {
+    pub fn new() -> Update {
+        Update::default()
+    }
+
+    pub fn set(mut self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.set.push(SetAction::Value(attribute.into(), value));
+        self
+    }
+
+    pub fn set_if_not_exists(mut self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.set.push(SetAction::IfNotExists(attribute.into(), value));
+        self
+    }
+
+    pub fn append_to_list(mut self, attribute: impl Into<AttributePath>, values: Vec<AttributeValue>) -> Self {
+        self.set.push(SetAction::AppendToList(attribute.into(), values));
+        self
+    }
+
+    pub fn remove(mut self, attribute: impl Into<AttributePath>) -> Self {
+        self.remove.push(attribute.into());
+        self
+    }
+
+    // Adds to a number or to a set, creating the attribute if it does not exist
+    pub fn add(mut self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.add.push((attribute.into(), value));
+        self
+    }
+
+    // Atomic counter, use a negative value to decrement
+    pub fn increment(self, attribute: impl Into<AttributePath>, by: i64) -> Self {
+        self.add(attribute, AttributeValue::N(by.to_string()))
+    }
+
+    pub fn add_to_string_set(self, attribute: impl Into<AttributePath>, values: Vec<String>) -> Self {
+        self.add(attribute, AttributeValue::Ss(values))
+    }
+
+    // Removes elements from a set
+    pub fn delete(mut self, attribute: impl Into<AttributePath>, value: AttributeValue) -> Self {
+        self.delete.push((attribute.into(), value));
+        self
+    }
+
+    pub fn remove_from_string_set(self, attribute: impl Into<AttributePath>, values: Vec<String>) -> Self {
+        self.delete(attribute, AttributeValue::Ss(values))
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.set.is_empty() && self.remove.is_empty() && self.add.is_empty() && self.delete.is_empty()
+    }
+
+    // An empty update would create the item without changing it, and DynamoDB rejects empty sets
+    pub(crate) fn validate(&self) -> Result<(), FluffError> {
+        let invalid = |description: &str| FluffError::new_u16(400, "InvalidUpdate", description, false);
+        if self.is_empty() {
+            return Err(invalid("The update has no action"));
+        }
+
+        let values = self.set.iter().filter_map(|action| match action {
+            SetAction::Value(attribute, value) | SetAction::IfNotExists(attribute, value) => Some((attribute, value)),
+            SetAction::AppendToList(_, _) => None,
+        });
+        let values = values.chain(self.add.iter().map(|(attribute, value)| (attribute, value)));
+        let mut values = values.chain(self.delete.iter().map(|(attribute, value)| (attribute, value)));
+        match values.find(|(_, value)| is_empty_set(value)) {
+            Some((attribute, _)) => Err(invalid("Sets cannot be empty").add_context(&attribute.to_string())),
+            None => Ok(()),
+        }
+    }
+
+    pub(crate) fn render(&self, attributes: &mut ExpressionAttributes) -> Option<String> {
+        let mut sections = Vec::new();
+
+        if !self.set.is_empty() {
+            let actions: Vec<String> = self
+                .set
+                .iter()
+                .map(|action| match action {
+                    SetAction::Value(attribute, value) => {
+                        format!("{} = {}", attributes.name(attribute), attributes.value(value.clone()))
+                    }
+                    SetAction::IfNotExists(attribute, value) => {
+                        let name = attributes.name(attribute);
+                        format!("{} = if_not_exists({}, {})", name, name, attributes.value(value.clone()))
+                    }
+                    SetAction::AppendToList(attribute, values) => {
+                        let name = attributes.name(attribute);
+                        let empty = attributes.value(AttributeValue::L(vec![]));
+                        format!(
+                            "{} = list_append(if_not_exists({}, {}), {})",
+                            name,
+                            name,
+                            empty,
+                            attributes.value(AttributeValue::L(values.clone()))
+                        )
+                    }
+                })
+                .collect();
+            sections.push(format!("SET {}", actions.join(", ")));
+        }
+
+        if !self.remove.is_empty() {
+            let actions: Vec<String> = self
+                .remove
+                .iter()
+                .map(|attribute| attributes.name(attribute))
+                .collect();
+            sections.push(format!("REMOVE {}", actions.join(", ")));
+        }
+
+        for (keyword, actions) in [("ADD", &self.add), ("DELETE", &self.delete)] {
+            if !actions.is_empty() {
+                let actions: Vec<String> = actions
+                    .iter()
+                    .map(|(attribute, value)| {
+                        format!("{} {}", attributes.name(attribute), attributes.value(value.clone()))
+                    })
+                    .collect();
+                sections.push(format!("{} {}", keyword, actions.join(", ")));
+            }
+        }
+
+        Some(sections.join(" ")).filter(|expression| !expression.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use aws_sdk_dynamodb::types::AttributeValue;
+
+    use super::{lookup, AttributePath, Condition, ExpressionAttributes, Update};
+
+    #[test]
+    fn dotted_strings_are_nested_paths() {
+        let mut attributes = ExpressionAttributes::default();
+        let expression = Update::new()
+            .set("profile.name", AttributeValue::S(String::from("a")))
+            .render(&mut attributes);
+        let (names, _) = attributes.into_parts();
+
+        assert_eq!(expression.as_deref(), Some("SET #fluff_n0.#fluff_n1 = :fluff_v0"));
+        assert_eq!(names.unwrap()["#fluff_n1"], "name");
+    }
+
+    #[test]
+    fn top_level_names_keep_their_dots() {
+        let mut attributes = ExpressionAttributes::default();
+        let expression = Condition::new()
+            .attribute_exists(AttributePath::top_level("stats.v2").child("count.total"))
+            .render(&mut attributes);
+        let (names, _) = attributes.into_parts();
+        let names = names.unwrap();
+
+        assert_eq!(expression.as_deref(), Some("attribute_exists(#fluff_n0.#fluff_n1)"));
+        assert_eq!(names["#fluff_n0"], "stats.v2");
+        assert_eq!(names["#fluff_n1"], "count.total");
+    }
+
+    #[test]
+    fn empty_updates_are_rejected() {
+        assert_eq!(Update::new().validate().unwrap_err().error_name, "InvalidUpdate");
+
+        let err = Update::new().add_to_string_set("tags", vec![]).validate().unwrap_err();
+        assert_eq!(err.http_code, 400);
+        assert_eq!(err.context, vec![String::from("tags")]);
+        assert!(Update::new().remove_from_string_set("tags", vec![String::from("a")]).validate().is_ok());
+    }
+
+    #[test]
+    fn conditions_evaluate_top_level_names() {
+        let item = HashMap::from([(String::from("a.b"), AttributeValue::N(String::from("2")))]);
+
+        assert!(lookup(&item, &AttributePath::top_level("a.b")).is_some());
+        assert!(lookup(&item, &AttributePath::from("a.b")).is_none());
+        let condition = Condition::new().greater_than(AttributePath::top_level("a.b"), AttributeValue::N(String::from("1")));
+        assert!(condition.evaluate(Some(&item)).unwrap());
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::{BTreeMap, HashMap};
+
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+
+use crate::errors::FluffError;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum KeyValue {
+    S(String),
+    N(String),
+    B(Vec<u8>),
+}
+
+impl KeyValue {
+    fn from_attribute(value: &AttributeValue) -> Option<KeyValue> {
+        match value {
+            AttributeValue::S(v) => Some(KeyValue::S(v.clone())),
+            AttributeValue::N(v) => Some(KeyValue::N(v.clone())),
+            AttributeValue::B(v) => Some(KeyValue::B(v.as_ref().to_vec())),
+            _ => None,
+        }
+    }
+
+    fn into_attribute(self) -> AttributeValue {
+        match self {
+            KeyValue::S(v) => AttributeValue::S(v),
+            KeyValue::N(v) => AttributeValue::N(v),
+            KeyValue::B(v) => AttributeValue::B(Blob::new(v)),
+        }
+    }
+}
+
+// Hashable primary key of an item, made of its partition key and optional sort key
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PrimaryKey(BTreeMap<String, KeyValue>);
+
+impl PrimaryKey {
+    pub fn new(partition_key: &str, value: KeyValue) -> PrimaryKey {
+        PrimaryKey(BTreeMap::from([(String::from(partition_key), value)]))
+    }
+
+    pub fn string(partition_key: &str, value: &str) -> PrimaryKey {
+        PrimaryKey::new(partition_key, KeyValue::S(String::from(value)))
+    }
+
+    pub fn with_sort_key(mut self, sort_key: &str, value: KeyValue) -> Self {
+        self.0.insert(String::from(sort_key), value);
+        self
+    }
+
+    // Extracts the key attributes named in `key_names` from a full item
+    pub fn from_item(key_names: &[&str], item: &HashMap<String, AttributeValue>) -> Result<PrimaryKey, FluffError> {
+        let mut key = BTreeMap::new();
+        for name in key_names {
+            let value = item
+                .get(*name)
+                .and_then(KeyValue::from_attribute)
+                .ok_or_else(|| {
+                    FluffError::new_u16(500, "DatabaseError", "Key not found in database item", true)
+                        .add_context(name)
+                })?;
+            key.insert(String::from(*name), value);
+        }
+        Ok(PrimaryKey(key))
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.0.keys().map(String::as_str).collect()
+    }
+
+    pub fn to_attributes(&self) -> HashMap<String, AttributeValue> {
+        self.clone().into()
+    }
+
+    // The same key with numbers in a canonical form, e.g. "1.0", "1" and "1e0" are the same number
+    pub(crate) fn normalized(&self) -> PrimaryKey {
+        let values = self.0.iter().map(|(name, value)| {
+            let value = match value {
+                KeyValue::N(number) => KeyValue::N(canonical_number(number)),
+                value => value.clone(),
+            };
+            (name.clone(), value)
+        });
+        PrimaryKey(values.collect())
+    }
+}
+
+// `<digits>e<exponent>` without leading or trailing zeros, numbers that do not parse are kept as is
+fn canonical_number(number: &str) -> String {
+    let number = number.trim();
+    let (negative, unsigned) = match number.strip_prefix('-') {
+        Some(unsigned) => (true, unsigned),
+        None => (false, number.strip_prefix('+').unwrap_or(number)),
+    };
+    let (mantissa, exponent) = match unsigned.split_once(['e', 'E']) {
+        Some((mantissa, exponent)) => match exponent.parse::<i64>() {
+            Ok(exponent) => (mantissa, exponent),
+            Err(_) => return String::from(number),
+        },
+        None => (unsigned, 0),
+    };
+    let (integer, fraction) = mantissa.split_once('.').unwrap_or((mantissa, ""));
+    let valid = !(integer.is_empty() && fraction.is_empty())
+        && integer.chars().chain(fraction.chars()).all(|c| c.is_ascii_digit());
+    if !valid {
+        return String::from(number);
+    }
+
+    let digits = format!("{}{}", integer, fraction);
+    let digits = digits.trim_start_matches('0');
+    if digits.is_empty() {
+        return String::from("0");
+    }
+    let trimmed = digits.trim_end_matches('0');
+    let exponent = exponent - fraction.len() as i64 + (digits.len() - trimmed.len()) as i64;
+    format!("{}{}e{}", if negative { "-" } else { "" }, trimmed, exponent)
+}
+
+impl TryFrom<&HashMap<String, AttributeValue>> for PrimaryKey {
+    type Error = FluffError;
+
+    fn try_from(keys: &HashMap<String, AttributeValue>) -> Result<Self, Self::Error> {
+        let names: Vec<&str> = keys.keys().map(String::as_str).collect();
+        PrimaryKey::from_item(&names, keys).map_err(|err| {
+            FluffError::new_u16(400, "InvalidKey", "Primary key values must be strings, numbers or binaries", false)
+                .add_context(&err.context.join(", "))
+        })
+    }
+}
+
+impl From<PrimaryKey> for HashMap<String, AttributeValue> {
+    fn from(key: PrimaryKey) -> Self {
+        key.0
+            .into_iter()
+            .map(|(name, value)| (name, value.into_attribute()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numbers_are_normalized() {
+        for (number, canonical) in [
+            ("1", "1e0"),
+            ("1.0", "1e0"),
+            ("+1e0", "1e0"),
+            ("10", "1e1"),
+            ("0.10", "1e-1"),
+            ("-002.50", "-25e-1"),
+            ("0", "0"),
+            ("-0.000", "0"),
+            ("abc", "abc"),
+        ] {
+            assert_eq!(canonical_number(number), canonical, "{}", number);
+        }
+    }
+
+    #[test]
+    fn normalized_keys_only_change_numbers() {
+        let key = PrimaryKey::string("id", "1.0").with_sort_key("round", KeyValue::N(String::from("2.0")));
+        let same = PrimaryKey::string("id", "1.0").with_sort_key("round", KeyValue::N(String::from("2")));
+        assert_ne!(key, same);
+        assert_eq!(key.normalized(), same.normalized());
+        assert_ne!(key.normalized(), PrimaryKey::string("id", "1").with_sort_key("round", KeyValue::N(String::from("2"))).normalized());
+    }
+}
@@ -0,0 +1,490 @@
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::future::Future;
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use futures::stream::{self, Stream, TryStreamExt};
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::dynamodb::expiring::is_expired;
+use crate::services::aws::dynamodb::expression::compare_values;
+use crate::services::aws::dynamodb::{database_error, DynamoItem};
+
+type Page = (Vec<HashMap<String, AttributeValue>>, Option<HashMap<String, AttributeValue>>);
+
+#[derive(Debug, Clone)]
+pub enum SortKeyCondition {
+    Equal(AttributeValue),
+    LessThan(AttributeValue),
+    LessThanOrEqual(AttributeValue),
+    GreaterThan(AttributeValue),
+    GreaterThanOrEqual(AttributeValue),
+    Between(AttributeValue, AttributeValue),
+    BeginsWith(String),
+}
+
+impl SortKeyCondition {
+    fn expression(&self) -> &'static str {
+        match self {
+            SortKeyCondition::Equal(_) => "#fluff_sk = :fluff_sk",
+            SortKeyCondition::LessThan(_) => "#fluff_sk < :fluff_sk",
+            SortKeyCondition::LessThanOrEqual(_) => "#fluff_sk <= :fluff_sk",
+            SortKeyCondition::GreaterThan(_) => "#fluff_sk > :fluff_sk",
+            SortKeyCondition::GreaterThanOrEqual(_) => "#fluff_sk >= :fluff_sk",
+            SortKeyCondition::Between(_, _) => "#fluff_sk BETWEEN :fluff_sk AND :fluff_sk_end",
+            SortKeyCondition::BeginsWith(_) => "begins_with(#fluff_sk, :fluff_sk)",
+        }
+    }
+
+    pub(crate) fn matches(&self, value: &AttributeValue) -> bool {
+        let ordering = |expected: &AttributeValue| compare_values(value, expected);
+        match self {
+            SortKeyCondition::Equal(v) => ordering(v) == Some(Ordering::Equal),
+            SortKeyCondition::LessThan(v) => ordering(v) == Some(Ordering::Less),
+            SortKeyCondition::LessThanOrEqual(v) => matches!(ordering(v), Some(Ordering::Less | Ordering::Equal)),
+            SortKeyCondition::GreaterThan(v) => ordering(v) == Some(Ordering::Greater),
+            SortKeyCondition::GreaterThanOrEqual(v) => {
+                matches!(ordering(v), Some(Ordering::Greater | Ordering::Equal))
+            }
+            SortKeyCondition::Between(low, high) => {
+                matches!(ordering(low), Some(Ordering::Greater | Ordering::Equal))
+                    && matches!(ordering(high), Some(Ordering::Less | Ordering::Equal))
+            }
+            SortKeyCondition::BeginsWith(prefix) => value.as_s().is_ok_and(|v| v.starts_with(prefix.as_str())),
+        }
+    }
+
+    fn values(&self) -> Vec<(String, AttributeValue)> {
+        match self {
+            SortKeyCondition::Equal(v)
+            | SortKeyCondition::LessThan(v)
+            | SortKeyCondition::LessThanOrEqual(v)
+            | SortKeyCondition::GreaterThan(v)
+            | SortKeyCondition::GreaterThanOrEqual(v) => vec![(String::from(":fluff_sk"), v.clone())],
+            SortKeyCondition::Between(low, high) => vec![
+                (String::from(":fluff_sk"), low.clone()),
+                (String::from(":fluff_sk_end"), high.clone()),
+            ],
+            SortKeyCondition::BeginsWith(prefix) => {
+                vec![(String::from(":fluff_sk"), AttributeValue::S(prefix.clone()))]
+            }
+        }
+    }
+}
+
+// Options shared by queries and scans
+#[derive(Debug, Clone, Default)]
+struct ReadOptions {
+    index_name: Option<String>,
+    filter_expression: Option<String>,
+    projection: Vec<String>,
+    expression_names: HashMap<String, String>,
+    expression_values: HashMap<String, AttributeValue>,
+    limit: Option<usize>,
+    page_size: Option<i32>,
+    consistent: bool,
+    skip_expired: bool,
+}
+
+impl ReadOptions {
+    fn projection_expression(&self) -> Option<String> {
+        if self.projection.is_empty() {
+            return None;
+        }
+        let placeholders: Vec<String> = (0..self.projection.len())
+            .map(|i| format!("#fluff_p{}", i))
+            .collect();
+        Some(placeholders.join(", "))
+    }
+
+    fn expression_names(&self, mut names: HashMap<String, String>) -> Option<HashMap<String, String>> {
+        names.extend(self.expression_names.clone());
+        for (i, attribute) in self.projection.iter().enumerate() {
+            names.insert(format!("#fluff_p{}", i), attribute.clone());
+        }
+        Some(names).filter(|names| !names.is_empty())
+    }
+
+    fn expression_values(
+        &self,
+        mut values: HashMap<String, AttributeValue>,
+    ) -> Option<HashMap<String, AttributeValue>> {
+        values.extend(self.expression_values.clone());
+        Some(values).filter(|values| !values.is_empty())
+    }
+}
+
+// Key condition of a `QueryRequest`
+#[derive(Debug, Clone)]
+pub struct Query {
+    partition_key: (String, AttributeValue),
+    sort_key: Option<(String, SortKeyCondition)>,
+    scan_forward: bool,
+}
+
+// A `ScanRequest` reads the whole table or index
+#[derive(Debug, Clone)]
+pub struct Scan;
+
+// Builder shared by queries and scans, see `QueryRequest` and `ScanRequest`
+#[derive(Debug, Clone)]
+pub struct ReadRequest<K> {
+    table: String,
+    kind: K,
+    options: ReadOptions,
+}
+
+pub type QueryRequest = ReadRequest<Query>;
+pub type ScanRequest = ReadRequest<Scan>;
+
+impl<K> ReadRequest<K> {
+    pub fn index(mut self, index_name: &str) -> Self {
+        self.options.index_name = Some(String::from(index_name));
+        self
+    }
+
+    // Placeholders used in the filter must be declared with `expression_name` and `expression_value`
+    pub fn filter(mut self, filter_expression: &str) -> Self {
+        self.options.filter_expression = Some(String::from(filter_expression));
+        self
+    }
+
+    pub fn expression_name(mut self, placeholder: &str, attribute: &str) -> Self {
+        self.options
+            .expression_names
+            .insert(String::from(placeholder), String::from(attribute));
+        self
+    }
+
+    pub fn expression_value(mut self, placeholder: &str, value: AttributeValue) -> Self {
+        self.options
+            .expression_values
+            .insert(String::from(placeholder), value);
+        self
+    }
+
+    pub fn projection(mut self, attributes: &[&str]) -> Self {
+        self.options.projection = attributes.iter().map(|v| String::from(*v)).collect();
+        self
+    }
+
+    // Maximum number of items returned across all pages
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.options.limit = Some(limit);
+        self
+    }
+
+    // Maximum number of items evaluated by DynamoDB for each page
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.options.page_size = Some(page_size);
+        self
+    }
+
+    pub fn consistent(mut self, consistent: bool) -> Self {
+        self.options.consistent = consistent;
+        self
+    }
+
+    // Skips items whose `expires_at` has passed but which DynamoDB has not reaped yet,
+    // only for tables with TTL enabled on that attribute
+    pub fn skip_expired(mut self) -> Self {
+        self.options.skip_expired = true;
+        self
+    }
+
+    pub(crate) fn with_table(mut self, table: &str) -> Self {
+        self.table = String::from(table);
+        self
+    }
+
+    pub(crate) fn item_limit(&self) -> Option<usize> {
+        self.options.limit
+    }
+
+    pub(crate) fn filter_expression(&self) -> Option<&str> {
+        self.options.filter_expression.as_deref()
+    }
+
+    pub(crate) fn skips_expired(&self) -> bool {
+        self.options.skip_expired
+    }
+
+    pub(crate) fn index_name(&self) -> Option<&str> {
+        self.options.index_name.as_deref()
+    }
+
+    pub(crate) fn projected_attributes(&self) -> &[String] {
+        &self.options.projection
+    }
+}
+
+impl QueryRequest {
+    pub fn new(table: &str, partition_key: &str, value: AttributeValue) -> QueryRequest {
+        ReadRequest {
+            table: String::from(table),
+            kind: Query {
+                partition_key: (String::from(partition_key), value),
+                sort_key: None,
+                scan_forward: true,
+            },
+            options: ReadOptions::default(),
+        }
+    }
+
+    pub fn sort_key(mut self, sort_key: &str, condition: SortKeyCondition) -> Self {
+        self.kind.sort_key = Some((String::from(sort_key), condition));
+        self
+    }
+
+    pub fn descending(mut self) -> Self {
+        self.kind.scan_forward = false;
+        self
+    }
+
+    pub(crate) fn partition_key(&self) -> (&str, &AttributeValue) {
+        (&self.kind.partition_key.0, &self.kind.partition_key.1)
+    }
+
+    pub(crate) fn sort_key_condition(&self) -> Option<(&str, &SortKeyCondition)> {
+        self.kind.sort_key.as_ref().map(|(name, condition)| (name.as_str(), condition))
+    }
+
+    pub(crate) fn is_descending(&self) -> bool {
+        !self.kind.scan_forward
+    }
+
+    fn key_condition_expression(&self) -> String {
+        match &self.kind.sort_key {
+            Some((_, condition)) => format!("#fluff_pk = :fluff_pk AND {}", condition.expression()),
+            None => String::from("#fluff_pk = :fluff_pk"),
+        }
+    }
+}
+
+impl ScanRequest {
+    pub fn new(table: &str) -> ScanRequest {
+        ReadRequest {
+            table: String::from(table),
+            kind: Scan,
+            options: ReadOptions::default(),
+        }
+    }
+}
+
+trait FetchPage: Clone {
+    fn fetch_page(
+        &self,
+        start_key: Option<HashMap<String, AttributeValue>>,
+    ) -> impl Future<Output = Result<Page, FluffError>>;
+}
+
+impl FetchPage for QueryRequest {
+    async fn fetch_page(&self, start_key: Option<HashMap<String, AttributeValue>>) -> Result<Page, FluffError> {
+        let client = FluffContext::shared().await.dynamodb();
+
+        let mut names = HashMap::from([(String::from("#fluff_pk"), self.kind.partition_key.0.clone())]);
+        let mut values = HashMap::from([(String::from(":fluff_pk"), self.kind.partition_key.1.clone())]);
+        if let Some((sort_key, condition)) = &self.kind.sort_key {
+            names.insert(String::from("#fluff_sk"), sort_key.clone());
+            values.extend(condition.values());
+        }
+
+        let output = client
+            .query()
+            .table_name(&self.table)
+            .set_index_name(self.options.index_name.clone())
+            .key_condition_expression(self.key_condition_expression())
+            .set_filter_expression(self.options.filter_expression.clone())
+            .set_projection_expression(self.options.projection_expression())
+            .set_expression_attribute_names(self.options.expression_names(names))
+            .set_expression_attribute_values(self.options.expression_values(values))
+            .set_limit(self.options.page_size)
+            .consistent_read(self.options.consistent)
+            .scan_index_forward(self.kind.scan_forward)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await
+            .map_err(|err| database_error(&self.table, "Failed to query items in database", err))?;
+
+        Ok((output.items.unwrap_or_default(), output.last_evaluated_key))
+    }
+}
+
+impl FetchPage for ScanRequest {
+    async fn fetch_page(&self, start_key: Option<HashMap<String, AttributeValue>>) -> Result<Page, FluffError> {
+        let client = FluffContext::shared().await.dynamodb();
+
+        let output = client
+            .scan()
+            .table_name(&self.table)
+            .set_index_name(self.options.index_name.clone())
+            .set_filter_expression(self.options.filter_expression.clone())
+            .set_projection_expression(self.options.projection_expression())
+            .set_expression_attribute_names(self.options.expression_names(HashMap::new()))
+            .set_expression_attribute_values(self.options.expression_values(HashMap::new()))
+            .set_limit(self.options.page_size)
+            .consistent_read(self.options.consistent)
+            .set_exclusive_start_key(start_key)
+            .send()
+            .await
+            .map_err(|err| database_error(&self.table, "Failed to scan items in database", err))?;
+
+        Ok((output.items.unwrap_or_default(), output.last_evaluated_key))
+    }
+}
+
+struct PageState {
+    start_key: Option<HashMap<String, AttributeValue>>,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+// Follows LastEvaluatedKey until the last page or until the item limit is reached
+fn paginate<F, Fut>(limit: Option<usize>, skip_expired: bool, fetch: F) -> impl Stream<Item = Result<DynamoItem, FluffError>>
+where
+    F: Fn(Option<HashMap<String, AttributeValue>>) -> Fut,
+    Fut: Future<Output = Result<Page, FluffError>>,
+{
+    let state = PageState {
+        start_key: None,
+        remaining: limit,
+        done: false,
+    };
+
+    stream::try_unfold(state, move |state| {
+        let page = if state.done || state.remaining == Some(0) {
+            None
+        } else {
+            Some(fetch(state.start_key))
+        };
+
+        async move {
+            let (mut items, last_key) = match page {
+                Some(page) => page.await?,
+                None => return Ok::<_, FluffError>(None),
+            };
+            if skip_expired {
+                items.retain(|item| !is_expired(item));
+            }
+            let remaining = state.remaining.map(|remaining| {
+                items.truncate(remaining);
+                remaining - items.len()
+            });
+            let last_key = last_key.filter(|key| !key.is_empty());
+
+            Ok(Some((
+                items,
+                PageState {
+                    done: last_key.is_none(),
+                    start_key: last_key,
+                    remaining,
+                },
+            )))
+        }
+    })
+    .map_ok(|items| stream::iter(items.into_iter().map(|data| Ok(DynamoItem::from(data)))))
+    .try_flatten()
+}
+
+fn read_stream<K>(request: ReadRequest<K>) -> impl Stream<Item = Result<DynamoItem, FluffError>>
+where
+    ReadRequest<K>: FetchPage,
+{
+    let (limit, skip_expired) = (request.options.limit, request.options.skip_expired);
+    paginate(limit, skip_expired, move |start_key| {
+        let request = request.clone();
+        async move { request.fetch_page(start_key).await }
+    })
+}
+
+pub fn query_stream(request: QueryRequest) -> impl Stream<Item = Result<DynamoItem, FluffError>> {
+    read_stream(request)
+}
+
+pub async fn query(request: &QueryRequest) -> Result<Vec<DynamoItem>, FluffError> {
+    query_stream(request.clone()).try_collect().await
+}
+
+pub fn scan_stream(request: ScanRequest) -> impl Stream<Item = Result<DynamoItem, FluffError>> {
+    read_stream(request)
+}
+
+pub async fn scan(request: &ScanRequest) -> Result<Vec<DynamoItem>, FluffError> {
+    scan_stream(request.clone()).try_collect().await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn item(id: u32, expires_at: Option<u64>) -> HashMap<String, AttributeValue> {
+        let mut item = HashMap::from([(String::from("id"), AttributeValue::N(id.to_string()))]);
+        if let Some(expires_at) = expires_at {
+            item.insert(String::from("expires_at"), AttributeValue::N(expires_at.to_string()));
+        }
+        item
+    }
+
+    fn page_key(page: usize) -> HashMap<String, AttributeValue> {
+        HashMap::from([(String::from("page"), AttributeValue::N(page.to_string()))])
+    }
+
+    // Reads `pages` in order and returns the ids read with the start key of every fetch
+    fn read(pages: Vec<Page>, limit: Option<usize>, skip_expired: bool) -> (Vec<u32>, Vec<Option<usize>>) {
+        let fetches = Mutex::new(Vec::new());
+        let stream = paginate(limit, skip_expired, |start_key| {
+            let index = start_key.map(|key| key["page"].as_n().unwrap().parse::<usize>().unwrap());
+            fetches.lock().unwrap().push(index);
+            let page = pages[index.unwrap_or(0)].clone();
+            async move { Ok(page) }
+        });
+        let items: Vec<DynamoItem> = block_on(stream.try_collect()).unwrap();
+        let ids = items.iter().map(|item| item.get_u64("id").unwrap() as u32).collect();
+        (ids, fetches.into_inner().unwrap())
+    }
+
+    #[test]
+    fn follows_last_evaluated_key_to_the_last_page() {
+        let pages = vec![
+            (vec![item(1, None), item(2, None)], Some(page_key(1))),
+            (vec![item(3, None)], Some(page_key(2))),
+            (vec![], None),
+        ];
+
+        assert_eq!(read(pages, None, false), (vec![1, 2, 3], vec![None, Some(1), Some(2)]));
+    }
+
+    #[test]
+    fn empty_last_evaluated_key_ends_the_read() {
+        let pages = vec![(vec![item(1, None)], Some(HashMap::new()))];
+
+        assert_eq!(read(pages, None, false), (vec![1], vec![None]));
+    }
+
+    #[test]
+    fn limit_stops_without_fetching_more_pages() {
+        let pages = vec![
+            (vec![item(1, None), item(2, None)], Some(page_key(1))),
+            (vec![item(3, None), item(4, None)], Some(page_key(2))),
+            (vec![item(5, None)], None),
+        ];
+
+        assert_eq!(read(pages, Some(3), false), (vec![1, 2, 3], vec![None, Some(1)]));
+    }
+
+    #[test]
+    fn expired_items_do_not_count_toward_the_limit() {
+        let pages = vec![
+            (vec![item(1, Some(1)), item(2, None)], Some(page_key(1))),
+            (vec![item(3, Some(1)), item(4, Some(u64::MAX)), item(5, None)], None),
+        ];
+
+        assert_eq!(read(pages.clone(), Some(2), true), (vec![2, 4], vec![None, Some(1)]));
+        assert_eq!(read(pages, Some(2), false), (vec![1, 2], vec![None]));
+    }
+}
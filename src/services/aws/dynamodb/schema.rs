@@ -0,0 +1,293 @@
+use std::time::Duration;
+
+use aws_sdk_dynamodb::types::{
+    AttributeDefinition, BillingMode, GlobalSecondaryIndex, KeySchemaElement, KeyType, Projection, ProjectionType,
+    ScalarAttributeType, TableStatus, TimeToLiveSpecification,
+};
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::dynamodb::{database_error, invalid_request};
+
+const TABLE_ACTIVE_ATTEMPTS: u32 = 60;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeyAttribute {
+    pub name: String,
+    pub attribute_type: ScalarAttributeType,
+}
+
+impl KeyAttribute {
+    pub fn new(name: &str, attribute_type: ScalarAttributeType) -> KeyAttribute {
+        KeyAttribute {
+            name: String::from(name),
+            attribute_type,
+        }
+    }
+
+    fn definition(&self) -> Result<AttributeDefinition, FluffError> {
+        AttributeDefinition::builder()
+            .attribute_name(&self.name)
+            .attribute_type(self.attribute_type.clone())
+            .build()
+            .map_err(invalid_request)
+    }
+}
+
+fn key_schema(partition_key: &KeyAttribute, sort_key: &Option<KeyAttribute>) -> Result<Vec<KeySchemaElement>, FluffError> {
+    let mut elements = vec![KeySchemaElement::builder()
+        .attribute_name(&partition_key.name)
+        .key_type(KeyType::Hash)
+        .build()
+        .map_err(invalid_request)?];
+    if let Some(sort_key) = sort_key {
+        elements.push(
+            KeySchemaElement::builder()
+                .attribute_name(&sort_key.name)
+                .key_type(KeyType::Range)
+                .build()
+                .map_err(invalid_request)?,
+        );
+    }
+    Ok(elements)
+}
+
+// Global secondary index, all attributes are projected into it
+#[derive(Debug, Clone, PartialEq)]
+pub struct GlobalIndex {
+    pub name: String,
+    pub partition_key: KeyAttribute,
+    pub sort_key: Option<KeyAttribute>,
+}
+
+impl GlobalIndex {
+    pub fn new(name: &str, partition_key: KeyAttribute) -> GlobalIndex {
+        GlobalIndex {
+            name: String::from(name),
+            partition_key,
+            sort_key: None,
+        }
+    }
+
+    pub fn sort_key(mut self, sort_key: KeyAttribute) -> Self {
+        self.sort_key = Some(sort_key);
+        self
+    }
+
+    fn to_sdk(&self) -> Result<GlobalSecondaryIndex, FluffError> {
+        GlobalSecondaryIndex::builder()
+            .index_name(&self.name)
+            .set_key_schema(Some(key_schema(&self.partition_key, &self.sort_key)?))
+            .projection(Projection::builder().projection_type(ProjectionType::All).build())
+            .build()
+            .map_err(invalid_request)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableSchema {
+    pub name: String,
+    pub partition_key: KeyAttribute,
+    pub sort_key: Option<KeyAttribute>,
+    pub global_indexes: Vec<GlobalIndex>,
+    pub ttl_attribute: Option<String>,
+}
+
+impl TableSchema {
+    pub fn new(name: &str, partition_key: KeyAttribute) -> TableSchema {
+        TableSchema {
+            name: String::from(name),
+            partition_key,
+            sort_key: None,
+            global_indexes: vec![],
+            ttl_attribute: None,
+        }
+    }
+
+    pub fn sort_key(mut self, sort_key: KeyAttribute) -> Self {
+        self.sort_key = Some(sort_key);
+        self
+    }
+
+    pub fn global_index(mut self, index: GlobalIndex) -> Self {
+        self.global_indexes.push(index);
+        self
+    }
+
+    pub fn ttl_attribute(mut self, attribute: &str) -> Self {
+        self.ttl_attribute = Some(String::from(attribute));
+        self
+    }
+
+    pub fn key_names(&self) -> Vec<&str> {
+        let mut names = vec![self.partition_key.name.as_str()];
+        if let Some(sort_key) = &self.sort_key {
+            names.push(sort_key.name.as_str());
+        }
+        names
+    }
+
+    // Every attribute used as a key by the table or its indexes, declared once
+    fn attribute_definitions(&self) -> Result<Vec<AttributeDefinition>, FluffError> {
+        let mut attributes: Vec<&KeyAttribute> = vec![];
+        let keys = std::iter::once(&self.partition_key)
+            .chain(self.sort_key.iter())
+            .chain(self.global_indexes.iter().flat_map(|index| {
+                std::iter::once(&index.partition_key).chain(index.sort_key.iter())
+            }));
+        for key in keys {
+            if !attributes.iter().any(|attribute| attribute.name == key.name) {
+                attributes.push(key);
+            }
+        }
+        attributes.into_iter().map(KeyAttribute::definition).collect()
+    }
+}
+
+pub async fn table_exists(table: &str) -> Result<bool, FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    match client.describe_table().table_name(table).send().await {
+        Ok(_) => Ok(true),
+        Err(err) if err.as_service_error().is_some_and(|err| err.is_resource_not_found_exception()) => Ok(false),
+        Err(err) => Err(database_error(table, "Failed to describe table in database", err)),
+    }
+}
+
+async fn wait_until_active(table: &str) -> Result<(), FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    for _ in 0..TABLE_ACTIVE_ATTEMPTS {
+        let output = client
+            .describe_table()
+            .table_name(table)
+            .send()
+            .await
+            .map_err(|err| database_error(table, "Failed to describe table in database", err))?;
+        if output.table.and_then(|table| table.table_status) == Some(TableStatus::Active) {
+            return Ok(());
+        }
+        tokio::time::sleep(Duration::from_millis(500)).await;
+    }
+
+    Err(FluffError::new_u16(
+        504,
+        "DatabaseTableNotReady",
+        "The database table did not become active in time",
+        true,
+    )
+    .add_context(table))
+}
+
+// Creates the table with on-demand billing and waits until it can be used
+pub async fn create_table(schema: &TableSchema) -> Result<(), FluffError> {
+    let client = FluffContext::shared().await.dynamodb();
+
+    let global_indexes = schema
+        .global_indexes
+        .iter()
+        .map(GlobalIndex::to_sdk)
+        .collect::<Result<Vec<GlobalSecondaryIndex>, FluffError>>()?;
+
+    client
+        .create_table()
+        .table_name(&schema.name)
+        .billing_mode(BillingMode::PayPerRequest)
+        .set_key_schema(Some(key_schema(&schema.partition_key, &schema.sort_key)?))
+        .set_attribute_definitions(Some(schema.attribute_definitions()?))
+        .set_global_secondary_indexes(Some(global_indexes).filter(|indexes| !indexes.is_empty()))
+        .send()
+        .await
+        .map_err(|err| database_error(&schema.name, "Failed to create table in database", err))?;
+
+    wait_until_active(&schema.name).await?;
+
+    if let Some(attribute) = &schema.ttl_attribute {
+        client
+            .update_time_to_live()
+            .table_name(&schema.name)
+            .time_to_live_specification(
+                TimeToLiveSpecification::builder()
+                    .attribute_name(attribute)
+                    .enabled(true)
+                    .build()
+                    .map_err(invalid_request)?,
+            )
+            .send()
+            .await
+            .map_err(|err| database_error(&schema.name, "Failed to enable time to live in database", err))?;
+    }
+
+    Ok(())
+}
+
+// Returns the names of the tables that were created
+pub async fn create_missing_tables(schemas: &[TableSchema]) -> Result<Vec<String>, FluffError> {
+    let mut created = vec![];
+    for schema in schemas {
+        if !table_exists(&schema.name).await? {
+            create_table(schema).await?;
+            created.push(schema.name.clone());
+        }
+    }
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn definitions(schema: &TableSchema) -> Vec<(String, ScalarAttributeType)> {
+        schema
+            .attribute_definitions()
+            .unwrap()
+            .into_iter()
+            .map(|definition| (String::from(definition.attribute_name()), definition.attribute_type().clone()))
+            .collect()
+    }
+
+    #[test]
+    fn shared_key_attributes_are_declared_once() {
+        let schema = TableSchema::new("scores", KeyAttribute::new("player", ScalarAttributeType::S))
+            .sort_key(KeyAttribute::new("round", ScalarAttributeType::N))
+            .global_index(
+                GlobalIndex::new("by_round", KeyAttribute::new("round", ScalarAttributeType::N))
+                    .sort_key(KeyAttribute::new("points", ScalarAttributeType::N)),
+            )
+            .global_index(
+                GlobalIndex::new("by_team", KeyAttribute::new("team", ScalarAttributeType::S))
+                    .sort_key(KeyAttribute::new("player", ScalarAttributeType::S)),
+            );
+
+        assert_eq!(
+            definitions(&schema),
+            vec![
+                (String::from("player"), ScalarAttributeType::S),
+                (String::from("round"), ScalarAttributeType::N),
+                (String::from("points"), ScalarAttributeType::N),
+                (String::from("team"), ScalarAttributeType::S),
+            ]
+        );
+    }
+
+    #[test]
+    fn table_without_sort_key() {
+        let schema = TableSchema::new("users", KeyAttribute::new("id", ScalarAttributeType::S));
+
+        assert_eq!(definitions(&schema), vec![(String::from("id"), ScalarAttributeType::S)]);
+        assert_eq!(schema.key_names(), vec!["id"]);
+        let elements = key_schema(&schema.partition_key, &schema.sort_key).unwrap();
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].key_type(), &KeyType::Hash);
+    }
+
+    #[test]
+    fn sort_key_is_the_range_key() {
+        let schema = TableSchema::new("scores", KeyAttribute::new("player", ScalarAttributeType::S))
+            .sort_key(KeyAttribute::new("round", ScalarAttributeType::N));
+        let elements = key_schema(&schema.partition_key, &schema.sort_key).unwrap();
+
+        assert_eq!(schema.key_names(), vec!["player", "round"]);
+        assert_eq!((elements[1].attribute_name(), elements[1].key_type()), ("round", &KeyType::Range));
+    }
+}
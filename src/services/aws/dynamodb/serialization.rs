@@ -0,0 +1,1203 @@
+use std::collections::HashMap;
+use std::fmt::{self, Display};
+
+use aws_sdk_dynamodb::primitives::Blob;
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+use serde::ser::{self, Serialize};
+
+use crate::errors::FluffError;
+
+const STRING_SET_TOKEN: &str = "__fluff_dynamodb_string_set";
+const NUMBER_SET_TOKEN: &str = "__fluff_dynamodb_number_set";
+const BINARY_SET_TOKEN: &str = "__fluff_dynamodb_binary_set";
+
+pub fn to_item<T: Serialize + ?Sized>(value: &T) -> Result<HashMap<String, AttributeValue>, FluffError> {
+    match to_attribute_value(value)? {
+        AttributeValue::M(item) => Ok(item),
+        _ => Err(FluffError::new_u16(
+            500,
+            "DynamoSerializationError",
+            "Value cannot be serialized as a database item",
+            false,
+        )
+        .add_context("value is not a struct or a map")),
+    }
+}
+
+pub fn to_attribute_value<T: Serialize + ?Sized>(value: &T) -> Result<AttributeValue, FluffError> {
+    value.serialize(Serializer).map_err(|err| {
+        FluffError::new_u16(
+            500,
+            "DynamoSerializationError",
+            "Value cannot be serialized as a database item",
+            false,
+        )
+        .add_context(&err.path())
+        .add_context(&err.message)
+    })
+}
+
+pub fn from_item<T: DeserializeOwned>(item: HashMap<String, AttributeValue>) -> Result<T, FluffError> {
+    from_attribute_value(AttributeValue::M(item))
+}
+
+pub fn from_attribute_value<T: DeserializeOwned>(value: AttributeValue) -> Result<T, FluffError> {
+    T::deserialize(Deserializer::new(value)).map_err(|err| {
+        FluffError::new_u16(
+            500,
+            "DynamoDeserializationError",
+            "Database item cannot be deserialized",
+            false,
+        )
+        .add_context(&err.path())
+        .add_context(&err.message)
+    })
+}
+
+// Use with `#[serde(with = "string_set")]` to store a collection of strings as a DynamoDB SS.
+// Empty collections are stored as NULL since DynamoDB rejects empty sets, so an optional
+// empty set is read back as None.
+pub mod string_set {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(super::STRING_SET_TOKEN, value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + Default,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+    }
+}
+
+// Use with `#[serde(with = "number_set")]` to store a collection of numbers as a DynamoDB NS.
+// Empty collections are stored as NULL since DynamoDB rejects empty sets.
+pub mod number_set {
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: Serialize + ?Sized,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(super::NUMBER_SET_TOKEN, value)
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: Deserialize<'de> + Default,
+        D: Deserializer<'de>,
+    {
+        Option::<T>::deserialize(deserializer).map(Option::unwrap_or_default)
+    }
+}
+
+// Use with `#[serde(with = "binary_set")]` on a collection of `Vec<u8>` to store it as a DynamoDB BS.
+// Empty collections are stored as NULL since DynamoDB rejects empty sets.
+pub mod binary_set {
+    use serde::ser::SerializeSeq;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    struct BinaryElements<'a, T: ?Sized>(&'a T);
+
+    impl<'a, T> Serialize for BinaryElements<'a, T>
+    where
+        &'a T: IntoIterator,
+        <&'a T as IntoIterator>::Item: AsRef<[u8]>,
+        T: ?Sized,
+    {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            let mut seq = serializer.serialize_seq(None)?;
+            for element in self.0 {
+                seq.serialize_element(&super::binary::Bytes(element.as_ref()))?;
+            }
+            seq.end()
+        }
+    }
+
+    pub fn serialize<'a, T, S>(value: &'a T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        &'a T: IntoIterator,
+        <&'a T as IntoIterator>::Item: AsRef<[u8]>,
+        T: ?Sized,
+        S: Serializer,
+    {
+        serializer.serialize_newtype_struct(super::BINARY_SET_TOKEN, &BinaryElements(value))
+    }
+
+    pub fn deserialize<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+    where
+        T: FromIterator<Vec<u8>>,
+        D: Deserializer<'de>,
+    {
+        let elements = Option::<Vec<super::binary::ByteBuf>>::deserialize(deserializer)?;
+        Ok(elements.unwrap_or_default().into_iter().map(|element| element.0).collect())
+    }
+}
+
+// Use with `#[serde(with = "binary")]` to store bytes as a DynamoDB B instead of a list of numbers.
+pub mod binary {
+    use std::fmt;
+
+    use serde::de::{SeqAccess, Visitor};
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub(super) struct Bytes<'a>(pub &'a [u8]);
+
+    impl Serialize for Bytes<'_> {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(self.0)
+        }
+    }
+
+    pub(super) struct ByteBuf(pub Vec<u8>);
+
+    impl<'de> Deserialize<'de> for ByteBuf {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            deserializer.deserialize_byte_buf(ByteBufVisitor).map(ByteBuf)
+        }
+    }
+
+    struct ByteBufVisitor;
+
+    impl<'de> Visitor<'de> for ByteBufVisitor {
+        type Value = Vec<u8>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            formatter.write_str("a byte array")
+        }
+
+        fn visit_bytes<E: serde::de::Error>(self, v: &[u8]) -> Result<Self::Value, E> {
+            Ok(v.to_vec())
+        }
+
+        fn visit_byte_buf<E: serde::de::Error>(self, v: Vec<u8>) -> Result<Self::Value, E> {
+            Ok(v)
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+            let mut bytes = Vec::new();
+            while let Some(byte) = seq.next_element()? {
+                bytes.push(byte);
+            }
+            Ok(bytes)
+        }
+    }
+
+    pub fn serialize<T, S>(value: &T, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        T: AsRef<[u8]> + ?Sized,
+        S: Serializer,
+    {
+        serializer.serialize_bytes(value.as_ref())
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        ByteBuf::deserialize(deserializer).map(|bytes| bytes.0)
+    }
+}
+
+#[derive(Debug)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+#[derive(Debug)]
+struct Error {
+    path: Vec<PathSegment>,
+    message: String,
+}
+
+impl Error {
+    fn new(message: impl Display) -> Error {
+        Error {
+            path: vec![],
+            message: message.to_string(),
+        }
+    }
+
+    // Segments are pushed while unwinding, so the outermost segment is the last one
+    fn at_key(mut self, key: &str) -> Error {
+        self.path.push(PathSegment::Key(String::from(key)));
+        self
+    }
+
+    fn at_index(mut self, index: usize) -> Error {
+        self.path.push(PathSegment::Index(index));
+        self
+    }
+
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+        for segment in self.path.iter().rev() {
+            match segment {
+                PathSegment::Key(key) if path.is_empty() => path.push_str(key),
+                PathSegment::Key(key) => {
+                    path.push('.');
+                    path.push_str(key);
+                }
+                PathSegment::Index(index) => path.push_str(&format!("[{}]", index)),
+            }
+        }
+        if path.is_empty() {
+            path.push_str("<root>");
+        }
+        path
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path(), self.message)
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(msg)
+    }
+}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(msg)
+    }
+}
+
+struct Serializer;
+
+fn number<T: Display>(value: T) -> Result<AttributeValue, Error> {
+    Ok(AttributeValue::N(value.to_string()))
+}
+
+// DynamoDB numbers range from 1E-130 to 9.99E+125 in magnitude, smaller floats are rounded to zero
+fn float<T: Display + Into<f64> + Copy>(value: T) -> Result<AttributeValue, Error> {
+    let magnitude = value.into().abs();
+    if !magnitude.is_finite() {
+        Err(Error::new("non-finite numbers cannot be stored in database"))
+    } else if magnitude >= 1e126 {
+        Err(Error::new("number is too large to be stored in database"))
+    } else if magnitude < 1e-130 {
+        number(0)
+    } else {
+        number(value)
+    }
+}
+
+fn into_set(name: &str, value: AttributeValue) -> Result<AttributeValue, Error> {
+    let elements = match value {
+        AttributeValue::L(elements) => elements,
+        // An optional set which is None
+        AttributeValue::Null(_) => return Ok(value),
+        _ => return Err(Error::new("sets must be serialized from a sequence")),
+    };
+    if elements.is_empty() {
+        return Ok(AttributeValue::Null(true));
+    }
+
+    match name {
+        STRING_SET_TOKEN => elements
+            .into_iter()
+            .map(|element| match element {
+                AttributeValue::S(v) => Ok(v),
+                _ => Err(Error::new("string set elements must be strings")),
+            })
+            .collect::<Result<_, _>>()
+            .map(AttributeValue::Ss),
+        NUMBER_SET_TOKEN => elements
+            .into_iter()
+            .map(|element| match element {
+                AttributeValue::N(v) => Ok(v),
+                _ => Err(Error::new("number set elements must be numbers")),
+            })
+            .collect::<Result<_, _>>()
+            .map(AttributeValue::Ns),
+        _ => elements
+            .into_iter()
+            .map(|element| match element {
+                AttributeValue::B(v) => Ok(v),
+                _ => Err(Error::new("binary set elements must be bytes")),
+            })
+            .collect::<Result<_, _>>()
+            .map(AttributeValue::Bs),
+    }
+}
+
+impl ser::Serializer for Serializer {
+    type Ok = AttributeValue;
+    type Error = Error;
+    type SerializeSeq = SerializeList;
+    type SerializeTuple = SerializeList;
+    type SerializeTupleStruct = SerializeList;
+    type SerializeTupleVariant = SerializeVariant<SerializeList>;
+    type SerializeMap = SerializeMap;
+    type SerializeStruct = SerializeMap;
+    type SerializeStructVariant = SerializeVariant<SerializeMap>;
+
+    fn serialize_bool(self, v: bool) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<AttributeValue, Error> {
+        number(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<AttributeValue, Error> {
+        float(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<AttributeValue, Error> {
+        float(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::S(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::S(String::from(v)))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::B(Blob::new(v)))
+    }
+
+    fn serialize_none(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Null(true))
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<AttributeValue, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Null(true))
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::Null(true))
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::S(String::from(variant)))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<AttributeValue, Error> {
+        match name {
+            STRING_SET_TOKEN | NUMBER_SET_TOKEN | BINARY_SET_TOKEN => into_set(name, value.serialize(self)?),
+            _ => value.serialize(self),
+        }
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<AttributeValue, Error> {
+        let value = value.serialize(self).map_err(|err| err.at_key(variant))?;
+        Ok(AttributeValue::M(HashMap::from([(String::from(variant), value)])))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeList, Error> {
+        Ok(SerializeList {
+            elements: Vec::with_capacity(len.unwrap_or(0)),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeList, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, len: usize) -> Result<SerializeList, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeList>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_seq(Some(len))?,
+        })
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap, Error> {
+        Ok(SerializeMap {
+            entries: HashMap::new(),
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMap, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeVariant<SerializeMap>, Error> {
+        Ok(SerializeVariant {
+            variant,
+            inner: self.serialize_map(Some(len))?,
+        })
+    }
+}
+
+struct SerializeList {
+    elements: Vec<AttributeValue>,
+}
+
+impl SerializeList {
+    fn push<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let index = self.elements.len();
+        let value = value.serialize(Serializer).map_err(|err| err.at_index(index))?;
+        self.elements.push(value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeSeq for SerializeList {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::L(self.elements))
+    }
+}
+
+impl ser::SerializeTuple for SerializeList {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::L(self.elements))
+    }
+}
+
+impl ser::SerializeTupleStruct for SerializeList {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.push(value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::L(self.elements))
+    }
+}
+
+struct SerializeMap {
+    entries: HashMap<String, AttributeValue>,
+    next_key: Option<String>,
+}
+
+impl SerializeMap {
+    fn insert<T: Serialize + ?Sized>(&mut self, key: String, value: &T) -> Result<(), Error> {
+        let value = value.serialize(Serializer).map_err(|err| err.at_key(&key))?;
+        self.entries.insert(key, value);
+        Ok(())
+    }
+}
+
+impl ser::SerializeMap for SerializeMap {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        self.next_key = Some(key.serialize(MapKeySerializer)?);
+        Ok(())
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let key = self
+            .next_key
+            .take()
+            .ok_or_else(|| Error::new("map value serialized before its key"))?;
+        self.insert(key, value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::M(self.entries))
+    }
+}
+
+impl ser::SerializeStruct for SerializeMap {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        self.insert(String::from(key), value)
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::M(self.entries))
+    }
+}
+
+struct SerializeVariant<T> {
+    variant: &'static str,
+    inner: T,
+}
+
+impl SerializeVariant<SerializeList> {
+    fn end_list(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::M(HashMap::from([(
+            String::from(self.variant),
+            AttributeValue::L(self.inner.elements),
+        )])))
+    }
+}
+
+impl ser::SerializeTupleVariant for SerializeVariant<SerializeList> {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        let variant = self.variant;
+        self.inner.push(value).map_err(|err| err.at_key(variant))
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        self.end_list()
+    }
+}
+
+impl ser::SerializeStructVariant for SerializeVariant<SerializeMap> {
+    type Ok = AttributeValue;
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Error> {
+        let variant = self.variant;
+        self.inner
+            .insert(String::from(key), value)
+            .map_err(|err| err.at_key(variant))
+    }
+
+    fn end(self) -> Result<AttributeValue, Error> {
+        Ok(AttributeValue::M(HashMap::from([(
+            String::from(self.variant),
+            AttributeValue::M(self.inner.entries),
+        )])))
+    }
+}
+
+struct MapKeySerializer;
+
+fn key_must_be_a_string() -> Error {
+    Error::new("map keys must be strings")
+}
+
+impl ser::Serializer for MapKeySerializer {
+    type Ok = String;
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<String, Error>;
+    type SerializeTuple = ser::Impossible<String, Error>;
+    type SerializeTupleStruct = ser::Impossible<String, Error>;
+    type SerializeTupleVariant = ser::Impossible<String, Error>;
+    type SerializeMap = ser::Impossible<String, Error>;
+    type SerializeStruct = ser::Impossible<String, Error>;
+    type SerializeStructVariant = ser::Impossible<String, Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_f32(self, _v: f32) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_f64(self, _v: f64) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_char(self, v: char) -> Result<String, Error> {
+        Ok(v.to_string())
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, Error> {
+        Ok(String::from(v))
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_none(self) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, Error> {
+        Ok(String::from(variant))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<String, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(key_must_be_a_string())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(key_must_be_a_string())
+    }
+}
+
+struct Deserializer {
+    value: AttributeValue,
+}
+
+impl Deserializer {
+    fn new(value: AttributeValue) -> Deserializer {
+        Deserializer { value }
+    }
+}
+
+fn visit_number<'de, V: Visitor<'de>>(number: &str, visitor: V) -> Result<V::Value, Error> {
+    if let Ok(v) = number.parse::<i64>() {
+        visitor.visit_i64(v)
+    } else if let Ok(v) = number.parse::<u64>() {
+        visitor.visit_u64(v)
+    } else if let Ok(v) = number.parse::<f64>() {
+        visitor.visit_f64(v)
+    } else {
+        Err(Error::new(format!("invalid number `{}`", number)))
+    }
+}
+
+fn type_name(value: &AttributeValue) -> &'static str {
+    match value {
+        AttributeValue::B(_) => "binary",
+        AttributeValue::Bool(_) => "boolean",
+        AttributeValue::Bs(_) => "binary set",
+        AttributeValue::L(_) => "list",
+        AttributeValue::M(_) => "map",
+        AttributeValue::N(_) => "number",
+        AttributeValue::Ns(_) => "number set",
+        AttributeValue::Null(_) => "null",
+        AttributeValue::S(_) => "string",
+        AttributeValue::Ss(_) => "string set",
+        _ => "unknown",
+    }
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            AttributeValue::S(v) => visitor.visit_string(v),
+            AttributeValue::N(v) => visit_number(&v, visitor),
+            AttributeValue::Bool(v) => visitor.visit_bool(v),
+            AttributeValue::Null(_) => visitor.visit_unit(),
+            AttributeValue::B(v) => visitor.visit_byte_buf(v.into_inner()),
+            AttributeValue::L(v) => visitor.visit_seq(SeqAccess::new(v)),
+            AttributeValue::M(v) => visitor.visit_map(MapAccess::new(v)),
+            AttributeValue::Ss(v) => visitor.visit_seq(SeqAccess::new(v.into_iter().map(AttributeValue::S).collect())),
+            AttributeValue::Ns(v) => visitor.visit_seq(SeqAccess::new(v.into_iter().map(AttributeValue::N).collect())),
+            AttributeValue::Bs(v) => visitor.visit_seq(SeqAccess::new(v.into_iter().map(AttributeValue::B).collect())),
+            _ => Err(Error::new("unsupported attribute type")),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.value {
+            AttributeValue::Null(_) => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.value {
+            AttributeValue::S(variant) => visitor.visit_enum(variant.into_deserializer()),
+            AttributeValue::M(map) if map.len() == 1 => {
+                let (variant, value) = map.into_iter().next().ok_or_else(|| Error::new("empty enum map"))?;
+                visitor.visit_enum(EnumAccess { variant, value })
+            }
+            other => Err(Error::new(format!(
+                "expected a string or a single-key map for an enum, found {}",
+                type_name(&other)
+            ))),
+        }
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        bytes byte_buf unit unit_struct seq tuple tuple_struct map struct identifier
+    }
+}
+
+struct SeqAccess {
+    elements: std::vec::IntoIter<AttributeValue>,
+    index: usize,
+}
+
+impl SeqAccess {
+    fn new(elements: Vec<AttributeValue>) -> SeqAccess {
+        SeqAccess {
+            elements: elements.into_iter(),
+            index: 0,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match self.elements.next() {
+            Some(value) => {
+                let index = self.index;
+                self.index += 1;
+                seed.deserialize(Deserializer::new(value))
+                    .map(Some)
+                    .map_err(|err| err.at_index(index))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapAccess {
+    entries: std::collections::hash_map::IntoIter<String, AttributeValue>,
+    next_value: Option<(String, AttributeValue)>,
+}
+
+impl MapAccess {
+    fn new(entries: HashMap<String, AttributeValue>) -> MapAccess {
+        MapAccess {
+            entries: entries.into_iter(),
+            next_value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, value)) => {
+                let deserialized = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(key.as_str()))?;
+                self.next_value = Some((key, value));
+                Ok(Some(deserialized))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (key, value) = self
+            .next_value
+            .take()
+            .ok_or_else(|| Error::new("map value deserialized before its key"))?;
+        seed.deserialize(Deserializer::new(value))
+            .map_err(|err| err.at_key(&key))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess {
+    variant: String,
+    value: AttributeValue,
+}
+
+impl<'de> de::EnumAccess<'de> for EnumAccess {
+    type Error = Error;
+    type Variant = VariantAccess;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(self, seed: V) -> Result<(V::Value, VariantAccess), Error> {
+        let variant = seed.deserialize(IntoDeserializer::<Error>::into_deserializer(self.variant.as_str()))?;
+        Ok((
+            variant,
+            VariantAccess {
+                variant: self.variant,
+                value: self.value,
+            },
+        ))
+    }
+}
+
+struct VariantAccess {
+    variant: String,
+    value: AttributeValue,
+}
+
+impl<'de> de::VariantAccess<'de> for VariantAccess {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, Error> {
+        let variant = self.variant;
+        seed.deserialize(Deserializer::new(self.value))
+            .map_err(|err| err.at_key(&variant))
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        let variant = self.variant;
+        de::Deserializer::deserialize_seq(Deserializer::new(self.value), visitor)
+            .map_err(|err| err.at_key(&variant))
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let variant = self.variant;
+        de::Deserializer::deserialize_map(Deserializer::new(self.value), visitor)
+            .map_err(|err| err.at_key(&variant))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+    use aws_sdk_dynamodb::primitives::Blob;
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use serde::{Deserialize, Serialize};
+
+    use super::{binary, binary_set, from_attribute_value, from_item, number_set, string_set, to_attribute_value, to_item};
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Address {
+        city: String,
+        zip: Option<u32>,
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    enum Role {
+        Viewer,
+        Moderator { level: u8 },
+    }
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Record {
+        id: String,
+        count: i64,
+        ratio: f64,
+        active: bool,
+        nickname: Option<String>,
+        address: Address,
+        history: Vec<Address>,
+        labels: BTreeMap<String, i32>,
+        roles: Vec<Role>,
+        #[serde(default, with = "string_set")]
+        tags: Vec<String>,
+        #[serde(default, with = "string_set")]
+        aliases: Option<Vec<String>>,
+        #[serde(default, with = "number_set")]
+        scores: BTreeSet<u32>,
+        #[serde(with = "binary")]
+        avatar: Vec<u8>,
+        #[serde(default, with = "binary_set")]
+        keys: Vec<Vec<u8>>,
+    }
+
+    fn record() -> Record {
+        Record {
+            id: String::from("u1"),
+            count: -42,
+            ratio: 0.25,
+            active: true,
+            nickname: None,
+            address: Address {
+                city: String::from("Lyon"),
+                zip: Some(69001),
+            },
+            history: vec![Address {
+                city: String::from("Paris"),
+                zip: None,
+            }],
+            labels: BTreeMap::from([(String::from("a"), 1), (String::from("b"), 2)]),
+            roles: vec![Role::Viewer, Role::Moderator { level: 3 }],
+            tags: vec![String::from("x"), String::from("y")],
+            aliases: Some(vec![String::from("z")]),
+            scores: BTreeSet::from([3, 1]),
+            avatar: vec![1, 2, 3],
+            keys: vec![vec![4], vec![5, 6]],
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let item = to_item(&record()).unwrap();
+        assert_eq!(item["id"], AttributeValue::S(String::from("u1")));
+        assert_eq!(item["count"], AttributeValue::N(String::from("-42")));
+        assert_eq!(item["ratio"], AttributeValue::N(String::from("0.25")));
+        assert_eq!(item["nickname"], AttributeValue::Null(true));
+        assert_eq!(item["tags"], AttributeValue::Ss(vec![String::from("x"), String::from("y")]));
+        assert_eq!(item["aliases"], AttributeValue::Ss(vec![String::from("z")]));
+        assert_eq!(item["avatar"], AttributeValue::B(Blob::new(vec![1, 2, 3])));
+        assert!(matches!(&item["address"], AttributeValue::M(address) if address.contains_key("city")));
+
+        assert_eq!(from_item::<Record>(item).unwrap(), record());
+    }
+
+    #[test]
+    fn empty_sets_are_null() {
+        let mut value = record();
+        value.tags = vec![];
+        value.aliases = None;
+        value.scores = BTreeSet::new();
+        value.keys = vec![];
+
+        let item = to_item(&value).unwrap();
+        for name in ["tags", "aliases", "scores", "keys"] {
+            assert_eq!(item[name], AttributeValue::Null(true), "{}", name);
+        }
+        assert_eq!(from_item::<Record>(item).unwrap(), value);
+    }
+
+    #[test]
+    fn empty_optional_set_is_read_as_none() {
+        let mut value = record();
+        value.aliases = Some(vec![]);
+
+        let item = to_item(&value).unwrap();
+        assert_eq!(item["aliases"], AttributeValue::Null(true));
+        assert_eq!(from_item::<Record>(item).unwrap().aliases, None);
+    }
+
+    #[test]
+    fn missing_sets_use_the_default() {
+        let mut item = to_item(&record()).unwrap();
+        item.remove("tags");
+        item.remove("aliases");
+
+        let value = from_item::<Record>(item).unwrap();
+        assert!(value.tags.is_empty());
+        assert_eq!(value.aliases, None);
+    }
+
+    #[test]
+    fn floats() {
+        assert_eq!(to_attribute_value(&1.5f32).unwrap(), AttributeValue::N(String::from("1.5")));
+        assert_eq!(to_attribute_value(&0.1f32).unwrap(), AttributeValue::N(String::from("0.1")));
+        assert_eq!(to_attribute_value(&-2.0e-5f64).unwrap(), AttributeValue::N(String::from("-0.00002")));
+        assert_eq!(to_attribute_value(&1e-200f64).unwrap(), AttributeValue::N(String::from("0")));
+        assert!(to_attribute_value(&1e125f64).is_ok());
+        assert!(to_attribute_value(&1e126f64).is_err());
+        assert!(to_attribute_value(&-1e300f64).is_err());
+        assert!(to_attribute_value(&f64::NAN).is_err());
+        assert!(to_attribute_value(&f32::INFINITY).is_err());
+
+        let value: f64 = from_attribute_value(AttributeValue::N(String::from("123.456"))).unwrap();
+        assert_eq!(value, 123.456);
+    }
+
+    #[test]
+    fn errors_name_the_attribute_path() {
+        let mut item = to_item(&record()).unwrap();
+        item.insert(
+            String::from("history"),
+            AttributeValue::L(vec![AttributeValue::M(HashMap::from([(
+                String::from("city"),
+                AttributeValue::N(String::from("1")),
+            )]))]),
+        );
+
+        let err = from_item::<Record>(item).unwrap_err();
+        assert_eq!(err.error_name, "DynamoDeserializationError");
+        assert!(err.context.iter().any(|context| context == "history[0].city"), "{:?}", err.context);
+    }
+
+    #[test]
+    fn non_struct_values_are_not_items() {
+        assert!(to_item(&5).is_err());
+        assert!(to_item(&vec![1, 2]).is_err());
+    }
+}
@@ -0,0 +1,291 @@
+use std::collections::HashMap;
+
+use aws_sdk_dynamodb::operation::transact_write_items::TransactWriteItemsError;
+use aws_sdk_dynamodb::types::{
+    AttributeValue, CancellationReason, ConditionCheck, Delete, Put, TransactWriteItem, Update as UpdateAction,
+};
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::dynamodb::expression::ExpressionAttributes;
+use crate::services::aws::dynamodb::{database_error, error_from_code, invalid_request, Condition, Update};
+
+const TRANSACTION_LIMIT: usize = 100;
+
+#[derive(Debug, Clone)]
+enum Operation {
+    Put(HashMap<String, AttributeValue>),
+    Update(HashMap<String, AttributeValue>, Update),
+    Delete(HashMap<String, AttributeValue>),
+    ConditionCheck(HashMap<String, AttributeValue>),
+}
+
+#[derive(Debug, Clone)]
+pub struct TransactWrite {
+    table: String,
+    operation: Operation,
+    condition: Condition,
+}
+
+impl TransactWrite {
+    pub fn put(table: &str, item: impl Into<HashMap<String, AttributeValue>>) -> TransactWrite {
+        TransactWrite::new(table, Operation::Put(item.into()))
+    }
+
+    pub fn update(table: &str, keys: HashMap<String, AttributeValue>, update: Update) -> TransactWrite {
+        TransactWrite::new(table, Operation::Update(keys, update))
+    }
+
+    pub fn delete(table: &str, keys: HashMap<String, AttributeValue>) -> TransactWrite {
+        TransactWrite::new(table, Operation::Delete(keys))
+    }
+
+    pub fn condition_check(table: &str, keys: HashMap<String, AttributeValue>, condition: Condition) -> TransactWrite {
+        TransactWrite::new(table, Operation::ConditionCheck(keys)).with_condition(condition)
+    }
+
+    fn new(table: &str, operation: Operation) -> TransactWrite {
+        TransactWrite {
+            table: String::from(table),
+            operation,
+            condition: Condition::new(),
+        }
+    }
+
+    pub fn with_condition(mut self, condition: Condition) -> Self {
+        self.condition = condition;
+        self
+    }
+
+    fn into_item(self) -> Result<TransactWriteItem, FluffError> {
+        let mut attributes = ExpressionAttributes::default();
+        let condition_expression = self.condition.render(&mut attributes);
+
+        let item = match self.operation {
+            Operation::Put(item) => {
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().put(
+                    Put::builder()
+                        .table_name(self.table)
+                        .set_item(Some(item))
+                        .set_condition_expression(condition_expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(invalid_request)?,
+                )
+            }
+            Operation::Update(keys, update) => {
+                update.validate().map_err(|err| err.add_context(&self.table))?;
+                let update_expression = update.render(&mut attributes);
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().update(
+                    UpdateAction::builder()
+                        .table_name(self.table)
+                        .set_key(Some(keys))
+                        .set_update_expression(update_expression)
+                        .set_condition_expression(condition_expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(invalid_request)?,
+                )
+            }
+            Operation::Delete(keys) => {
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().delete(
+                    Delete::builder()
+                        .table_name(self.table)
+                        .set_key(Some(keys))
+                        .set_condition_expression(condition_expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(invalid_request)?,
+                )
+            }
+            Operation::ConditionCheck(keys) => {
+                let (names, values) = attributes.into_parts();
+                TransactWriteItem::builder().condition_check(
+                    ConditionCheck::builder()
+                        .table_name(self.table)
+                        .set_key(Some(keys))
+                        .set_condition_expression(condition_expression)
+                        .set_expression_attribute_names(names)
+                        .set_expression_attribute_values(values)
+                        .build()
+                        .map_err(invalid_request)?,
+                )
+            }
+        };
+
+        Ok(item.build())
+    }
+}
+
+// Error of a canceled transaction, `operations` holds the failure of each operation in request order
+#[derive(Debug, Clone)]
+pub struct TransactionError {
+    pub error: FluffError,
+    pub operations: Vec<Option<FluffError>>,
+}
+
+impl From<TransactionError> for FluffError {
+    fn from(err: TransactionError) -> Self {
+        err.error
+    }
+}
+
+impl From<FluffError> for TransactionError {
+    fn from(error: FluffError) -> Self {
+        TransactionError {
+            error,
+            operations: vec![],
+        }
+    }
+}
+
+fn reason_to_error(table: &str, reason: &CancellationReason) -> Option<FluffError> {
+    let error = match reason.code() {
+        None | Some("None") => return None,
+        Some(code) => {
+            let error = error_from_code(Some(code), "Failed to write transaction in database").add_context(table);
+            // The code is the only hint about the cause of an unknown reason
+            match error.error_name.as_str() {
+                "DatabaseError" => error.add_context(code),
+                _ => error,
+            }
+        }
+    };
+
+    Some(match reason.message() {
+        Some(message) => error.add_context(message),
+        None => error,
+    })
+}
+
+// All operations succeed or none are applied, at most 100 operations per transaction
+pub async fn transact_write(operations: Vec<TransactWrite>) -> Result<(), TransactionError> {
+    if operations.len() > TRANSACTION_LIMIT {
+        return Err(FluffError::new_u16(
+            400,
+            "TransactionTooLarge",
+            "A transaction cannot contain more than 100 operations",
+            false,
+        )
+        .add_context(&operations.len().to_string())
+        .into());
+    }
+
+    let client = FluffContext::shared().await.dynamodb();
+
+    let tables: Vec<String> = operations.iter().map(|operation| operation.table.clone()).collect();
+    let items = operations
+        .into_iter()
+        .map(TransactWrite::into_item)
+        .collect::<Result<Vec<TransactWriteItem>, FluffError>>()?;
+
+    let err = match client.transact_write_items().set_transact_items(Some(items)).send().await {
+        Ok(_) => return Ok(()),
+        Err(err) => err,
+    };
+
+    let reasons = match err.as_service_error() {
+        Some(TransactWriteItemsError::TransactionCanceledException(err)) => Some(err.cancellation_reasons()),
+        _ => None,
+    };
+    let reasons = match reasons {
+        Some(reasons) => reasons,
+        None => {
+            return Err(database_error(&tables.join(", "), "Failed to write transaction in database", err).into())
+        }
+    };
+
+    Err(canceled_error(&tables, reasons, &err.to_string()))
+}
+
+// Maps the reason of each operation to its error, in request order
+fn canceled_error(tables: &[String], reasons: &[CancellationReason], cause: &str) -> TransactionError {
+    let operations: Vec<Option<FluffError>> = tables
+        .iter()
+        .enumerate()
+        .map(|(i, table)| reasons.get(i).and_then(|reason| reason_to_error(table, reason)))
+        .collect();
+
+    // The first failing operation decides the overall error
+    let error = operations
+        .iter()
+        .enumerate()
+        .find_map(|(i, error)| error.clone().map(|error| error.add_context(&format!("operation {}", i))))
+        .unwrap_or_else(|| {
+            FluffError::new_u16(
+                409,
+                "TransactionCanceled",
+                "The database transaction was canceled",
+                true,
+            )
+            .add_context(cause)
+        });
+
+    TransactionError { error, operations }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    fn reason(code: &str, message: Option<&str>) -> CancellationReason {
+        CancellationReason::builder().code(code).set_message(message.map(String::from)).build()
+    }
+
+    fn tables(count: usize) -> Vec<String> {
+        (0..count).map(|i| format!("table{}", i)).collect()
+    }
+
+    #[test]
+    fn reasons_map_to_the_error_of_each_operation() {
+        let reasons = [
+            reason("None", None),
+            reason("ConditionalCheckFailed", Some("The conditional request failed")),
+            reason("TransactionConflict", None),
+        ];
+        let err = canceled_error(&tables(3), &reasons, "canceled");
+
+        assert!(err.operations[0].is_none());
+        let conditional = err.operations[1].as_ref().unwrap();
+        assert_eq!((conditional.http_code, conditional.error_name.as_str()), (409, "ConditionalCheckFailed"));
+        assert_eq!(conditional.context, vec![String::from("table1"), String::from("The conditional request failed")]);
+        assert_eq!(err.operations[2].as_ref().unwrap().error_name, "TransactionConflict");
+
+        assert_eq!(err.error.error_name, "ConditionalCheckFailed");
+        assert_eq!(err.error.context.last().map(String::as_str), Some("operation 1"));
+    }
+
+    #[test]
+    fn unknown_reasons_keep_their_code() {
+        let err = canceled_error(&tables(1), &[reason("SomethingNew", None)], "canceled");
+
+        assert_eq!(err.error.error_name, "DatabaseError");
+        assert!(err.error.context.contains(&String::from("SomethingNew")));
+    }
+
+    #[test]
+    fn transaction_without_failing_operation_is_canceled() {
+        let err = canceled_error(&tables(2), &[reason("None", None)], "canceled");
+
+        assert_eq!(err.operations.len(), 2);
+        assert!(err.operations.iter().all(Option::is_none));
+        assert_eq!((err.error.http_code, err.error.error_name.as_str()), (409, "TransactionCanceled"));
+    }
+
+    #[test]
+    fn transactions_are_limited_to_100_operations() {
+        let operations = (0..101).map(|_| TransactWrite::delete("table", HashMap::new())).collect();
+        let err = block_on(transact_write(operations)).unwrap_err();
+
+        assert_eq!(err.error.error_name, "TransactionTooLarge");
+        assert!(err.operations.is_empty());
+    }
+}
@@ -1,40 +1,225 @@
+use aws_sdk_ssm::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_ssm::primitives::DateTime;
+use aws_sdk_ssm::types::{ParameterTier, ParameterType, ResourceTypeForTagging, Tag};
+use serde::de::DeserializeOwned;
+
 use crate::errors::FluffError;
-use aws_config::BehaviorVersion;
+use crate::services::aws::context::FluffContext;
+
+pub mod batch;
+pub mod cache;
+pub mod hierarchy;
+pub mod versions;
+
+pub use batch::{get_parameters_by_names, ParametersByNames};
+pub use cache::{get_cached_parameter, get_cached_value, ParameterCache};
+pub use hierarchy::from_parameters;
+pub use versions::{
+    get_parameter_history, get_parameter_label, get_parameter_version, label_parameter_version,
+    unlabel_parameter_version, ParameterVersion,
+};
+
+// A parameter with its metadata, `value` is decrypted only when requested
+#[derive(Debug, Clone, PartialEq)]
+pub struct Parameter {
+    pub name: String,
+    pub value: String,
+    pub parameter_type: Option<ParameterType>,
+    pub version: i64,
+    pub last_modified: Option<DateTime>,
+    pub data_type: Option<String>,
+}
+
+impl Parameter {
+    fn from_aws(parameter: aws_sdk_ssm::types::Parameter) -> Option<Parameter> {
+        Some(Parameter {
+            name: parameter.name?,
+            value: parameter.value?,
+            parameter_type: parameter.r#type,
+            version: parameter.version,
+            last_modified: parameter.last_modified_date,
+            data_type: parameter.data_type,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReadOptions {
+    pub(crate) decrypt: bool,
+    recursive: bool,
+}
+
+impl ReadOptions {
+    pub fn new() -> ReadOptions {
+        ReadOptions::default()
+    }
+
+    // Returns SecureString values in clear text, requires kms:Decrypt on their key
+    pub fn decrypt(mut self, decrypt: bool) -> Self {
+        self.decrypt = decrypt;
+        self
+    }
+
+    // Also reads the parameters of nested paths, only used when reading a path
+    pub fn recursive(mut self, recursive: bool) -> Self {
+        self.recursive = recursive;
+        self
+    }
+}
+
+// Maps the AWS error code of a failed call to the matching error
+pub(crate) fn parameter_error<E, R>(parameter_name: &str, description: &str, err: SdkError<E, R>) -> FluffError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+    R: std::fmt::Debug,
+{
+    let error = match err.code() {
+        Some("ParameterNotFound") | Some("ParameterVersionNotFound") => FluffError::new_u16(
+            404,
+            "ParameterNotFound",
+            "The requested parameter was not found",
+            false,
+        ),
+        Some("ParameterAlreadyExists") => FluffError::new_u16(
+            409,
+            "ParameterAlreadyExists",
+            "The parameter already exists",
+            false,
+        ),
+        Some("ParameterPatternMismatchException")
+        | Some("InvalidAllowedPatternException")
+        | Some("InvalidKeyId")
+        | Some("UnsupportedParameterType")
+        | Some("HierarchyLevelLimitExceededException")
+        | Some("ValidationException") => FluffError::new_u16(
+            400,
+            "InvalidParameterRequest",
+            "The Parameter Store request is invalid",
+            false,
+        ),
+        Some("ThrottlingException") | Some("TooManyUpdates") => FluffError::new_u16(
+            429,
+            "ParameterStoreThrottled",
+            "Parameter Store request rate exceeded",
+            true,
+        ),
+        Some("ParameterVersionLabelLimitExceeded") => FluffError::new_u16(
+            400,
+            "ParameterLabelLimitExceeded",
+            "A parameter version can have at most 10 labels",
+            false,
+        ),
+        Some("AccessDeniedException") => FluffError::new_u16(
+            403,
+            "ParameterStoreAccessDenied",
+            "Access to Parameter Store was denied",
+            false,
+        ),
+        _ => FluffError::new_u16(500, "ParameterStoreError", description, true),
+    }
+    .add_context(parameter_name);
+    match err.message() {
+        Some(message) => error.add_context(message),
+        None => error.add_context(&DisplayErrorContext(&err).to_string()),
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PutOptions {
+    overwrite: bool,
+    tier: ParameterTier,
+    data_type: String,
+    kms_key_id: Option<String>,
+    description: Option<String>,
+    allowed_pattern: Option<String>,
+    tags: Vec<(String, String)>,
+}
+
+impl Default for PutOptions {
+    fn default() -> Self {
+        PutOptions {
+            overwrite: true,
+            tier: ParameterTier::Standard,
+            data_type: String::from("text"),
+            kms_key_id: None,
+            description: None,
+            allowed_pattern: None,
+            tags: vec![],
+        }
+    }
+}
+
+impl PutOptions {
+    pub fn new() -> PutOptions {
+        PutOptions::default()
+    }
+
+    // Fails with 409 ParameterAlreadyExists instead of writing a new version
+    pub fn create_only(mut self) -> Self {
+        self.overwrite = false;
+        self
+    }
+
+    pub fn tier(mut self, tier: ParameterTier) -> Self {
+        self.tier = tier;
+        self
+    }
+
+    // Key used to encrypt SecureString values, defaults to the `alias/aws/ssm` key
+    pub fn kms_key_id(mut self, kms_key_id: &str) -> Self {
+        self.kms_key_id = Some(String::from(kms_key_id));
+        self
+    }
+
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(String::from(description));
+        self
+    }
+
+    // Regular expression the value must match, e.g. `^\d+$`
+    pub fn allowed_pattern(mut self, allowed_pattern: &str) -> Self {
+        self.allowed_pattern = Some(String::from(allowed_pattern));
+        self
+    }
+
+    // SSM only accepts tags in PutParameter on creation, so they are added with AddTagsToResource
+    // after the write when overwriting is allowed
+    pub fn tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((String::from(key), String::from(value)));
+        self
+    }
+
+    // The value is an AMI id, validated by SSM before the version becomes available
+    pub fn ec2_image(mut self) -> Self {
+        self.data_type = String::from("aws:ec2:image");
+        self
+    }
+}
 
 struct AwsParametersPage {
-    parameters: Vec<(String, String)>,
+    parameters: Vec<Parameter>,
     next_token: Option<String>,
 }
 
-pub async fn get_parameter(parameter_name: &str) -> Result<String, FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_ssm::Client::new(&config);
+pub async fn get_parameter(parameter_name: &str, options: &ReadOptions) -> Result<Parameter, FluffError> {
+    let client = FluffContext::shared().await.ssm();
 
     let aws_output = client
         .get_parameter()
         .name(parameter_name)
+        .with_decryption(options.decrypt)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(
-                500,
-                "ParameterStoreError",
-                "Unable to read Parameter Store",
-                true,
-            )
-            .add_context(&err.to_string())
-        })?;
+        .map_err(|err| parameter_error(parameter_name, "Unable to read Parameter Store", err))?;
 
-    if let Some(parameter) = aws_output.parameter {
-        if let Some(value) = parameter.value {
-            return Ok(value);
-        }
+    if let Some(parameter) = aws_output.parameter.and_then(Parameter::from_aws) {
+        return Ok(parameter);
     }
     Err(FluffError::new_u16(
         404,
         "ParameterNotFound",
         "The requested parameter was not found",
-        true,
+        false,
     )
     .add_context(parameter_name))
 }
@@ -43,6 +228,7 @@ async fn int_get_parameters_path(
     client: &aws_sdk_ssm::Client,
     token: &str,
     parameter_path: &str,
+    options: &ReadOptions,
 ) -> Result<AwsParametersPage, FluffError> {
     let mut response = AwsParametersPage {
         parameters: Vec::new(),
@@ -52,45 +238,34 @@ async fn int_get_parameters_path(
     let aws_output = client
         .get_parameters_by_path()
         .path(parameter_path)
+        .recursive(options.recursive)
+        .with_decryption(options.decrypt)
         .next_token(token)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(
-                500,
-                "ParameterStoreError",
-                "Unable to read Parameter Store",
-                true,
-            )
-            .add_context(&err.to_string())
-        })?;
+        .map_err(|err| parameter_error(parameter_path, "Unable to read Parameter Store", err))?;
 
     if let Some(next_token) = aws_output.next_token {
         response.next_token = Some(next_token);
     }
     if let Some(parameters) = aws_output.parameters {
-        for parameter in parameters {
-            if let Some(value) = parameter.value {
-                if let Some(name) = parameter.name {
-                    response.parameters.push((name, value));
-                }
-            }
-        }
+        response
+            .parameters
+            .extend(parameters.into_iter().filter_map(Parameter::from_aws));
     }
 
     Ok(response)
 }
 
-pub async fn get_parameters(parameter_path: &str) -> Result<Vec<(String, String)>, FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_ssm::Client::new(&config);
+pub async fn get_parameters(parameter_path: &str, options: &ReadOptions) -> Result<Vec<Parameter>, FluffError> {
+    let client = FluffContext::shared().await.ssm();
 
     let mut parameters = Vec::new();
     let mut next_token = String::from("");
 
     loop {
         let response =
-            int_get_parameters_path(&client, next_token.as_ref(), parameter_path).await?;
+            int_get_parameters_path(client, next_token.as_ref(), parameter_path, options).await?;
         parameters.extend(response.parameters);
         if let Some(token) = response.next_token {
             next_token = token;
@@ -102,57 +277,168 @@ pub async fn get_parameters(parameter_path: &str) -> Result<Vec<(String, String)
     Ok(parameters)
 }
 
+// Reads every parameter below `parameter_path` into `T`, e.g. `/fluff/prod/twitch/client_id`
+// fills the `client_id` field of the `twitch` field when loading `/fluff/prod/`.
+// SecureString parameters are decrypted.
+pub async fn load_parameters<T: DeserializeOwned>(parameter_path: &str) -> Result<T, FluffError> {
+    let options = ReadOptions::new().decrypt(true).recursive(true);
+    let parameters = get_parameters(parameter_path, &options)
+        .await?
+        .into_iter()
+        .map(|parameter| (parameter.name, parameter.value))
+        .collect();
+    from_parameters(parameter_path, parameters)
+}
+
+#[derive(Debug, Default, PartialEq)]
+struct PutTags {
+    with_value: Option<Vec<Tag>>,
+    after_put: Option<Vec<Tag>>,
+}
+
+// PutParameter rejects tags when it may overwrite, they are then added once the value is written
+fn split_tags(parameter_name: &str, options: &PutOptions) -> Result<PutTags, FluffError> {
+    if options.tags.is_empty() {
+        return Ok(PutTags::default());
+    }
+    let tags = options
+        .tags
+        .iter()
+        .map(|(key, value)| Tag::builder().key(key).value(value).build())
+        .collect::<Result<Vec<Tag>, _>>()
+        .map_err(|err| {
+            FluffError::new_u16(400, "InvalidParameterTag", "Parameter tags are invalid", false)
+                .add_context(parameter_name)
+                .add_context(&err.to_string())
+        })?;
+    Ok(match options.overwrite {
+        true => PutTags {
+            with_value: None,
+            after_put: Some(tags),
+        },
+        false => PutTags {
+            with_value: Some(tags),
+            after_put: None,
+        },
+    })
+}
+
+// When the put may overwrite and tagging fails, the new value is already written: the error
+// says which version was written so that only the tags need to be retried
 pub async fn put_parameters(
     parameter_name: &str,
     value: &str,
     ptype: aws_sdk_ssm::types::ParameterType,
-) -> Result<(), FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_ssm::Client::new(&config);
+    options: &PutOptions,
+) -> Result<i64, FluffError> {
+    let client = FluffContext::shared().await.ssm();
+    let tags = split_tags(parameter_name, options)?;
 
-    client
+    let aws_output = client
         .put_parameter()
         .name(parameter_name)
         .value(value)
         .r#type(ptype)
-        .overwrite(true)
-        .tier(aws_sdk_ssm::types::ParameterTier::Standard)
-        .data_type("text")
+        .overwrite(options.overwrite)
+        .tier(options.tier.clone())
+        .data_type(&options.data_type)
+        .set_key_id(options.kms_key_id.clone())
+        .set_description(options.description.clone())
+        .set_allowed_pattern(options.allowed_pattern.clone())
+        .set_tags(tags.with_value)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(
-                500,
-                "ParameterStoreError",
-                "Unable to write in Parameter Store",
-                true,
-            )
-            .add_context(parameter_name)
-            .add_context(&err.to_string())
-        })?;
+        .map_err(|err| parameter_error(parameter_name, "Unable to write in Parameter Store", err))?;
+    ParameterCache::shared().invalidate(parameter_name);
 
-    Ok(())
+    if let Some(tags) = tags.after_put {
+        client
+            .add_tags_to_resource()
+            .resource_type(ResourceTypeForTagging::Parameter)
+            .resource_id(parameter_name)
+            .set_tags(Some(tags))
+            .send()
+            .await
+            .map_err(|err| {
+                parameter_error(parameter_name, "Unable to tag the parameter in Parameter Store", err)
+                    .add_context(&format!("value written as version {}", aws_output.version))
+            })?;
+    }
+    Ok(aws_output.version)
 }
 
 pub async fn delete_parameters(parameter_name: &str) -> Result<(), FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_ssm::Client::new(&config);
+    let client = FluffContext::shared().await.ssm();
 
     client
         .delete_parameter()
         .name(parameter_name)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(
-                500,
-                "ParameterStoreError",
-                "Unable to delete in Parameter Store",
-                true,
-            )
-            .add_context(parameter_name)
-            .add_context(&err.to_string())
-        })?;
+        .map_err(|err| parameter_error(parameter_name, "Unable to delete in Parameter Store", err))?;
 
+    ParameterCache::shared().invalidate(parameter_name);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_ssm::error::{ErrorMetadata, SdkError};
+    use aws_sdk_ssm::operation::get_parameter::GetParameterError;
+
+    use super::*;
+
+    fn error(code: &str, message: Option<&str>) -> FluffError {
+        let mut metadata = ErrorMetadata::builder().code(code);
+        if let Some(message) = message {
+            metadata = metadata.message(message);
+        }
+        let err = SdkError::service_error(GetParameterError::generic(metadata.build()), ());
+        parameter_error("/fluff/test", "Unable to read Parameter Store", err)
+    }
+
+    #[test]
+    fn error_codes_are_mapped() {
+        for (code, http_code, error_name, can_retry) in [
+            ("ParameterNotFound", 404, "ParameterNotFound", false),
+            ("ParameterVersionNotFound", 404, "ParameterNotFound", false),
+            ("ParameterAlreadyExists", 409, "ParameterAlreadyExists", false),
+            ("ValidationException", 400, "InvalidParameterRequest", false),
+            ("ThrottlingException", 429, "ParameterStoreThrottled", true),
+            ("TooManyUpdates", 429, "ParameterStoreThrottled", true),
+            ("ParameterVersionLabelLimitExceeded", 400, "ParameterLabelLimitExceeded", false),
+            ("AccessDeniedException", 403, "ParameterStoreAccessDenied", false),
+            ("InternalServerError", 500, "ParameterStoreError", true),
+        ] {
+            let err = error(code, None);
+            assert_eq!((err.http_code, err.error_name.as_str(), err.can_retry), (http_code, error_name, can_retry), "{}", code);
+        }
+    }
+
+    fn tag_keys(tags: Option<Vec<Tag>>) -> Option<Vec<String>> {
+        tags.map(|tags| tags.iter().map(|tag| String::from(tag.key())).collect())
+    }
+
+    #[test]
+    fn tags_are_sent_with_the_value_only_when_creating() {
+        let options = PutOptions::new().tag("team", "fluff").tag("env", "prod");
+
+        let tags = split_tags("/fluff/test", &options).unwrap();
+        assert_eq!(tags.with_value, None);
+        assert_eq!(tag_keys(tags.after_put), Some(vec![String::from("team"), String::from("env")]));
+
+        let tags = split_tags("/fluff/test", &options.create_only()).unwrap();
+        assert_eq!(tag_keys(tags.with_value), Some(vec![String::from("team"), String::from("env")]));
+        assert_eq!(tags.after_put, None);
+
+        assert_eq!(split_tags("/fluff/test", &PutOptions::new()).unwrap(), PutTags::default());
+    }
+
+    #[test]
+    fn errors_name_the_parameter_and_keep_the_message() {
+        let err = error("ParameterNotFound", Some("No parameter /fluff/test"));
+
+        assert_eq!(err.context, vec![String::from("/fluff/test"), String::from("No parameter /fluff/test")]);
+        assert_eq!(error("InternalServerError", None).error_description, "Unable to read Parameter Store");
+    }
+}
@@ -0,0 +1,40 @@
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::parameter_store::{parameter_error, Parameter, ReadOptions};
+
+// GetParameters accepts at most 10 names per call
+const MAX_NAMES: usize = 10;
+
+#[derive(Debug, Clone, Default)]
+pub struct ParametersByNames {
+    pub parameters: Vec<Parameter>,
+    // Names that do not exist or are malformed, including versions and labels
+    pub invalid_names: Vec<String>,
+}
+
+// Reads several parameters by name, names can select a version or a label (`name:3`, `name:prod`)
+pub async fn get_parameters_by_names(names: &[&str], options: &ReadOptions) -> Result<ParametersByNames, FluffError> {
+    let client = FluffContext::shared().await.ssm();
+
+    let mut response = ParametersByNames::default();
+    for chunk in names.chunks(MAX_NAMES) {
+        let aws_output = client
+            .get_parameters()
+            .set_names(Some(chunk.iter().map(|name| String::from(*name)).collect()))
+            .with_decryption(options.decrypt)
+            .send()
+            .await
+            .map_err(|err| parameter_error(&chunk.join(","), "Unable to read Parameter Store", err))?;
+
+        if let Some(parameters) = aws_output.parameters {
+            response
+                .parameters
+                .extend(parameters.into_iter().filter_map(Parameter::from_aws));
+        }
+        if let Some(invalid_names) = aws_output.invalid_parameters {
+            response.invalid_names.extend(invalid_names);
+        }
+    }
+
+    Ok(response)
+}
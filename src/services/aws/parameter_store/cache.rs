@@ -0,0 +1,88 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use crate::errors::FluffError;
+use crate::services::aws::parameter_store::{self, Parameter, ReadOptions};
+use crate::services::cache::{lock, TtlCache};
+
+static SHARED_CACHE: OnceLock<ParameterCache> = OnceLock::new();
+
+// Caches Parameter Store reads in memory for the lifetime of the Lambda container. There is a single
+// instance, `ParameterCache::shared`, so that `put_parameters` and `delete_parameters` can invalidate it
+#[derive(Debug)]
+pub struct ParameterCache {
+    parameter_ttls: Mutex<HashMap<String, Duration>>,
+    entries: TtlCache<(String, bool), Parameter>,
+}
+
+impl ParameterCache {
+    fn new() -> ParameterCache {
+        ParameterCache {
+            parameter_ttls: Mutex::new(HashMap::new()),
+            entries: TtlCache::new("ParameterNotFound"),
+        }
+    }
+
+    pub fn shared() -> &'static ParameterCache {
+        SHARED_CACHE.get_or_init(ParameterCache::new)
+    }
+
+    // 5 minutes by default, see `TtlCache` for the other settings
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.entries.set_ttl(ttl);
+    }
+
+    pub fn set_not_found_ttl(&self, ttl: Duration) {
+        self.entries.set_not_found_ttl(ttl);
+    }
+
+    pub fn set_stale_ttl(&self, ttl: Duration) {
+        self.entries.set_stale_ttl(ttl);
+    }
+
+    // Overrides the TTL of one parameter, applied from its next refresh
+    pub fn set_parameter_ttl(&self, parameter_name: &str, ttl: Duration) {
+        lock(&self.parameter_ttls).insert(String::from(parameter_name), ttl);
+    }
+
+    pub async fn get_parameter(&self, parameter_name: &str, options: &ReadOptions) -> Result<Parameter, FluffError> {
+        let key = (String::from(parameter_name), options.decrypt);
+        let ttl = lock(&self.parameter_ttls).get(parameter_name).copied();
+
+        self.entries
+            .get(key, ttl, parameter_store::get_parameter(parameter_name, options))
+            .await
+    }
+
+    // Decrypted value of a parameter
+    pub async fn get_value(&self, parameter_name: &str) -> Result<String, FluffError> {
+        let options = ReadOptions::new().decrypt(true);
+        Ok(self.get_parameter(parameter_name, &options).await?.value)
+    }
+
+    // Also invalidates the versions and labels of the parameter (`name:3`, `name:prod`)
+    pub fn invalidate(&self, parameter_name: &str) {
+        let selector = format!("{}:", parameter_name);
+        self.entries
+            .retain(|(name, _)| name != parameter_name && !name.starts_with(&selector));
+    }
+
+    // Invalidates every parameter below a path
+    pub fn invalidate_path(&self, parameter_path: &str) {
+        let prefix = format!("{}/", parameter_path.trim_end_matches('/'));
+        self.entries.retain(|(name, _)| !name.starts_with(&prefix));
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+pub async fn get_cached_parameter(parameter_name: &str, options: &ReadOptions) -> Result<Parameter, FluffError> {
+    ParameterCache::shared().get_parameter(parameter_name, options).await
+}
+
+pub async fn get_cached_value(parameter_name: &str) -> Result<String, FluffError> {
+    ParameterCache::shared().get_value(parameter_name).await
+}
@@ -0,0 +1,593 @@
+use std::collections::BTreeMap;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+use serde::de::{self, DeserializeOwned, DeserializeSeed, IntoDeserializer, Visitor};
+
+use crate::errors::FluffError;
+
+// Builds a value from the parameters found under `parameter_path`.
+// Each path segment below it is a struct field or a map key, values are coerced to the requested types
+// and comma separated values (StringList parameters) can be read as lists.
+pub fn from_parameters<T: DeserializeOwned>(
+    parameter_path: &str,
+    parameters: Vec<(String, String)>,
+) -> Result<T, FluffError> {
+    let base = parameter_path.trim_end_matches('/');
+    let mut root = Node::Path(BTreeMap::new());
+    for (name, value) in parameters {
+        if let Some(relative) = name.strip_prefix(base) {
+            let segments = split(relative);
+            if !segments.is_empty() && (relative.starts_with('/') || base.is_empty()) {
+                root.insert(&segments, Node::Value(value)).map_err(|_| {
+                    FluffError::new_u16(
+                        500,
+                        "InvalidParameterHierarchy",
+                        "A parameter is both a value and a path",
+                        false,
+                    )
+                    .add_context(&name)
+                })?;
+            }
+        }
+    }
+
+    // A missing field stops the deserialization, so each one is replaced by a placeholder
+    // and the value is rebuilt until every missing parameter is known
+    let mut missing: Vec<String> = Vec::new();
+    loop {
+        let err = match T::deserialize(Deserializer::new(root.clone(), base)) {
+            Ok(value) if missing.is_empty() => return Ok(value),
+            Ok(_) => break,
+            Err(err) => err,
+        };
+        let name = err.parameter.clone().unwrap_or_else(|| String::from(base));
+        if err.missing.is_none() || missing.contains(&name) {
+            if missing.is_empty() {
+                return Err(FluffError::new_u16(
+                    500,
+                    "InvalidParameter",
+                    "A parameter does not have the expected type",
+                    false,
+                )
+                .add_context(&name)
+                .add_context(&err.message));
+            }
+            break;
+        }
+        if root.insert(&split(&name[base.len()..]), Node::Missing).is_err() {
+            break;
+        }
+        missing.push(name);
+    }
+
+    // Only the deepest paths are reported, a missing struct is reported through its fields
+    let mut error = FluffError::new_u16(
+        500,
+        "MissingParameters",
+        "Required parameters are missing from Parameter Store",
+        false,
+    );
+    for name in &missing {
+        let prefix = format!("{}/", name);
+        if !missing.iter().any(|other| other.starts_with(&prefix)) {
+            error = error.add_context(name);
+        }
+    }
+    Err(error)
+}
+
+fn split(path: &str) -> Vec<&str> {
+    path.split('/').filter(|segment| !segment.is_empty()).collect()
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Value(String),
+    Path(BTreeMap<String, Node>),
+    // Placeholder for a required parameter that does not exist
+    Missing,
+}
+
+impl Node {
+    fn insert(&mut self, segments: &[&str], node: Node) -> Result<(), ()> {
+        let Some((last, parents)) = segments.split_last() else {
+            return Err(());
+        };
+        let mut current = self;
+        for segment in parents {
+            if let Node::Missing = current {
+                *current = Node::Path(BTreeMap::new());
+            }
+            current = match current {
+                Node::Path(children) => children
+                    .entry(String::from(*segment))
+                    .or_insert_with(|| Node::Path(BTreeMap::new())),
+                _ => return Err(()),
+            };
+        }
+        if let Node::Missing = current {
+            *current = Node::Path(BTreeMap::new());
+        }
+        match current {
+            Node::Path(children) => match children.get(*last) {
+                None | Some(Node::Missing) => {
+                    children.insert(String::from(*last), node);
+                    Ok(())
+                }
+                Some(_) => Err(()),
+            },
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Error {
+    // Full name of the parameter, filled in while unwinding
+    parameter: Option<String>,
+    missing: Option<&'static str>,
+    message: String,
+}
+
+impl Error {
+    fn new(message: impl Display) -> Error {
+        Error {
+            parameter: None,
+            missing: None,
+            message: message.to_string(),
+        }
+    }
+
+    fn at(mut self, path: &str) -> Error {
+        if self.parameter.is_none() {
+            self.parameter = Some(match self.missing {
+                Some(field) => format!("{}/{}", path, field),
+                None => String::from(path),
+            });
+        }
+        self
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.parameter {
+            Some(parameter) => write!(f, "{}: {}", parameter, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::new(msg)
+    }
+
+    fn missing_field(field: &'static str) -> Self {
+        Error {
+            parameter: None,
+            missing: Some(field),
+            message: String::from("missing parameter"),
+        }
+    }
+}
+
+struct Deserializer {
+    node: Node,
+    path: String,
+}
+
+impl Deserializer {
+    fn new(node: Node, path: &str) -> Deserializer {
+        Deserializer {
+            node,
+            path: String::from(path),
+        }
+    }
+
+    fn parse<T: FromStr + Default>(self) -> Result<T, Error> {
+        match self.node {
+            Node::Value(value) => value.trim().parse().map_err(|_| {
+                Error::new(format!("`{}` is not a valid {}", value, std::any::type_name::<T>())).at(&self.path)
+            }),
+            Node::Missing => Ok(T::default()),
+            Node::Path(_) => Err(expected_value().at(&self.path)),
+        }
+    }
+}
+
+fn expected_value() -> Error {
+    Error::new("expected a parameter value, found a parameter path")
+}
+
+macro_rules! deserialize_parsed {
+    ($($method:ident => $visit:ident),* $(,)?) => {
+        $(
+            fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+                visitor.$visit(self.parse()?)
+            }
+        )*
+    };
+}
+
+impl<'de> de::Deserializer<'de> for Deserializer {
+    type Error = Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Value(value) => visitor.visit_string(value),
+            Node::Path(children) => visitor.visit_map(MapAccess::new(children, self.path)),
+            Node::Missing => visitor.visit_unit(),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Value(value) => match value.trim().to_lowercase().as_str() {
+                "true" | "yes" | "1" => visitor.visit_bool(true),
+                "false" | "no" | "0" => visitor.visit_bool(false),
+                _ => Err(Error::new(format!("`{}` is not a valid bool", value)).at(&self.path)),
+            },
+            Node::Missing => visitor.visit_bool(false),
+            Node::Path(_) => Err(expected_value().at(&self.path)),
+        }
+    }
+
+    deserialize_parsed! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+        deserialize_char => visit_char,
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Value(value) => visitor.visit_string(value),
+            Node::Missing => visitor.visit_string(String::new()),
+            Node::Path(_) => Err(expected_value().at(&self.path)),
+        }
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Value(value) => visitor.visit_byte_buf(value.into_bytes()),
+            Node::Missing => visitor.visit_byte_buf(vec![]),
+            Node::Path(_) => Err(expected_value().at(&self.path)),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Missing => visitor.visit_none(),
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(self, _name: &'static str, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    // A list is either a comma separated value or a path whose children are named 0, 1, 2...
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        let elements = match self.node {
+            Node::Value(value) if value.trim().is_empty() => vec![],
+            Node::Value(value) => value
+                .split(',')
+                .map(|element| Node::Value(String::from(element.trim())))
+                .collect(),
+            Node::Missing => vec![],
+            Node::Path(children) => {
+                let mut indexed = Vec::new();
+                for (key, child) in children {
+                    let index: usize = key.parse().map_err(|_| {
+                        Error::new("expected a list, found a parameter path with non numeric names")
+                            .at(&self.path)
+                    })?;
+                    indexed.push((index, child));
+                }
+                indexed.sort_by_key(|(index, _)| *index);
+                indexed.into_iter().map(|(_, child)| child).collect()
+            }
+        };
+        visitor.visit_seq(SeqAccess::new(elements, self.path))
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _len: usize, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Path(children) => visitor.visit_map(MapAccess::new(children, self.path)),
+            Node::Missing => visitor.visit_map(MapAccess::new(BTreeMap::new(), self.path)),
+            Node::Value(_) => Err(Error::new("expected a parameter path, found a parameter value").at(&self.path)),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        let path = self.path.clone();
+        self.deserialize_map(visitor).map_err(|err| err.at(&path))
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error> {
+        match self.node {
+            Node::Value(variant) => visitor
+                .visit_enum(IntoDeserializer::<Error>::into_deserializer(variant))
+                .map_err(|err| err.at(&self.path)),
+            _ => Err(Error::new("expected a parameter value naming an enum variant").at(&self.path)),
+        }
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Error> {
+        visitor.visit_unit()
+    }
+
+    serde::forward_to_deserialize_any! {
+        i128 u128
+    }
+}
+
+struct SeqAccess {
+    elements: std::vec::IntoIter<Node>,
+    path: String,
+    index: usize,
+}
+
+impl SeqAccess {
+    fn new(elements: Vec<Node>, path: String) -> SeqAccess {
+        SeqAccess {
+            elements: elements.into_iter(),
+            path,
+            index: 0,
+        }
+    }
+}
+
+impl<'de> de::SeqAccess<'de> for SeqAccess {
+    type Error = Error;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(&mut self, seed: T) -> Result<Option<T::Value>, Error> {
+        match self.elements.next() {
+            Some(node) => {
+                let path = format!("{}/{}", self.path, self.index);
+                self.index += 1;
+                seed.deserialize(Deserializer::new(node, &path))
+                    .map(Some)
+                    .map_err(|err| err.at(&path))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct MapAccess {
+    entries: std::collections::btree_map::IntoIter<String, Node>,
+    path: String,
+    next_value: Option<(String, Node)>,
+}
+
+impl MapAccess {
+    fn new(entries: BTreeMap<String, Node>, path: String) -> MapAccess {
+        MapAccess {
+            entries: entries.into_iter(),
+            path,
+            next_value: None,
+        }
+    }
+}
+
+impl<'de> de::MapAccess<'de> for MapAccess {
+    type Error = Error;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, Error> {
+        match self.entries.next() {
+            Some((key, node)) => {
+                let path = format!("{}/{}", self.path, key);
+                let key = seed
+                    .deserialize(IntoDeserializer::<Error>::into_deserializer(key))
+                    .map_err(|err| err.at(&path))?;
+                self.next_value = Some((path, node));
+                Ok(Some(key))
+            }
+            None => Ok(None),
+        }
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, Error> {
+        let (path, node) = self
+            .next_value
+            .take()
+            .ok_or_else(|| Error::new("value requested before its key"))?;
+        seed.deserialize(Deserializer::new(node, &path))
+            .map_err(|err| err.at(&path))
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::from_parameters;
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    #[serde(rename_all = "lowercase")]
+    enum Mode {
+        Live,
+        Maintenance,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Twitch {
+        client_id: String,
+        scopes: Vec<String>,
+    }
+
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Config {
+        port: u16,
+        ratio: f64,
+        debug: bool,
+        enabled: bool,
+        mode: Mode,
+        twitch: Twitch,
+        hosts: Vec<String>,
+        limits: HashMap<String, u32>,
+        #[serde(default)]
+        optional: Option<String>,
+    }
+
+    fn parameters(values: &[(&str, &str)]) -> Vec<(String, String)> {
+        values
+            .iter()
+            .map(|(name, value)| (format!("/fluff/prod/{}", name), String::from(*value)))
+            .collect()
+    }
+
+    fn complete() -> Vec<(String, String)> {
+        parameters(&[
+            ("port", "8080"),
+            ("ratio", "0.5"),
+            ("debug", "yes"),
+            ("enabled", "false"),
+            ("mode", "maintenance"),
+            ("twitch/client_id", "abc"),
+            ("twitch/scopes", "user:read,chat:edit"),
+            ("hosts/0", "a.example"),
+            ("hosts/1", "b.example"),
+            ("limits/events", "10"),
+            ("limits/users", "200"),
+        ])
+    }
+
+    #[test]
+    fn nested_paths_and_coercion() {
+        let config: Config = from_parameters("/fluff/prod/", complete()).unwrap();
+
+        assert_eq!(
+            config,
+            Config {
+                port: 8080,
+                ratio: 0.5,
+                debug: true,
+                enabled: false,
+                mode: Mode::Maintenance,
+                twitch: Twitch {
+                    client_id: String::from("abc"),
+                    scopes: vec![String::from("user:read"), String::from("chat:edit")],
+                },
+                hosts: vec![String::from("a.example"), String::from("b.example")],
+                limits: HashMap::from([(String::from("events"), 10), (String::from("users"), 200)]),
+                optional: None,
+            }
+        );
+    }
+
+    #[test]
+    fn path_without_trailing_slash_and_foreign_parameters() {
+        let mut values = complete();
+        values.push((String::from("/fluff/production/port"), String::from("1")));
+        values.push((String::from("/other/port"), String::from("2")));
+
+        let config: Config = from_parameters("/fluff/prod", values).unwrap();
+        assert_eq!(config.port, 8080);
+    }
+
+    #[test]
+    fn missing_parameters_are_all_listed() {
+        let values: Vec<(String, String)> = complete()
+            .into_iter()
+            .filter(|(name, _)| !name.ends_with("/port") && !name.contains("/twitch/"))
+            .collect();
+
+        let err = from_parameters::<Config>("/fluff/prod/", values).unwrap_err();
+        assert_eq!(err.error_name, "MissingParameters");
+        let mut missing = err.context.clone();
+        missing.sort();
+        assert_eq!(
+            missing,
+            vec![
+                String::from("/fluff/prod/port"),
+                String::from("/fluff/prod/twitch/client_id"),
+                String::from("/fluff/prod/twitch/scopes"),
+            ]
+        );
+    }
+
+    #[test]
+    fn invalid_values_name_the_parameter() {
+        let mut values = complete();
+        values[0].1 = String::from("eighty");
+
+        let err = from_parameters::<Config>("/fluff/prod/", values).unwrap_err();
+        assert_eq!(err.error_name, "InvalidParameter");
+        assert_eq!(err.context[0], "/fluff/prod/port");
+    }
+
+    #[test]
+    fn value_and_path_conflict() {
+        let mut values = complete();
+        values.push((String::from("/fluff/prod/port/extra"), String::from("1")));
+
+        let err = from_parameters::<Config>("/fluff/prod/", values).unwrap_err();
+        assert_eq!(err.error_name, "InvalidParameterHierarchy");
+    }
+}
@@ -0,0 +1,130 @@
+use aws_sdk_ssm::primitives::DateTime;
+use aws_sdk_ssm::types::ParameterType;
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::parameter_store::{self, parameter_error, Parameter, ParameterCache, ReadOptions};
+
+// One version of a parameter, as returned by its history
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParameterVersion {
+    pub name: String,
+    pub value: String,
+    pub version: i64,
+    pub labels: Vec<String>,
+    pub parameter_type: Option<ParameterType>,
+    pub description: Option<String>,
+    pub last_modified: Option<DateTime>,
+    pub last_modified_user: Option<String>,
+}
+
+pub async fn get_parameter_version(
+    parameter_name: &str,
+    version: i64,
+    options: &ReadOptions,
+) -> Result<Parameter, FluffError> {
+    parameter_store::get_parameter(&format!("{}:{}", parameter_name, version), options).await
+}
+
+pub async fn get_parameter_label(
+    parameter_name: &str,
+    label: &str,
+    options: &ReadOptions,
+) -> Result<Parameter, FluffError> {
+    parameter_store::get_parameter(&format!("{}:{}", parameter_name, label), options).await
+}
+
+// Every version of a parameter, oldest first
+pub async fn get_parameter_history(
+    parameter_name: &str,
+    options: &ReadOptions,
+) -> Result<Vec<ParameterVersion>, FluffError> {
+    let client = FluffContext::shared().await.ssm();
+
+    let mut versions = Vec::new();
+    let mut next_token: Option<String> = None;
+
+    loop {
+        let aws_output = client
+            .get_parameter_history()
+            .name(parameter_name)
+            .with_decryption(options.decrypt)
+            .set_next_token(next_token)
+            .send()
+            .await
+            .map_err(|err| parameter_error(parameter_name, "Unable to read Parameter Store", err))?;
+
+        for entry in aws_output.parameters.unwrap_or_default() {
+            if let (Some(name), Some(value)) = (entry.name, entry.value) {
+                versions.push(ParameterVersion {
+                    name,
+                    value,
+                    version: entry.version,
+                    labels: entry.labels.unwrap_or_default(),
+                    parameter_type: entry.r#type,
+                    description: entry.description,
+                    last_modified: entry.last_modified_date,
+                    last_modified_user: entry.last_modified_user,
+                });
+            }
+        }
+
+        next_token = aws_output.next_token;
+        if next_token.is_none() {
+            break;
+        }
+    }
+
+    Ok(versions)
+}
+
+// Attaches labels to a version, or to the latest one when `version` is None.
+// A label is moved if it was attached to another version, returns the labelled version.
+pub async fn label_parameter_version(
+    parameter_name: &str,
+    version: Option<i64>,
+    labels: &[&str],
+) -> Result<i64, FluffError> {
+    let client = FluffContext::shared().await.ssm();
+
+    let aws_output = client
+        .label_parameter_version()
+        .name(parameter_name)
+        .set_parameter_version(version)
+        .set_labels(Some(labels.iter().map(|label| String::from(*label)).collect()))
+        .send()
+        .await
+        .map_err(|err| parameter_error(parameter_name, "Unable to label a parameter version", err))?;
+
+    ParameterCache::shared().invalidate(parameter_name);
+
+    let invalid_labels = aws_output.invalid_labels.unwrap_or_default();
+    if !invalid_labels.is_empty() {
+        return Err(FluffError::new_u16(
+            400,
+            "InvalidParameterLabels",
+            "Labels cannot start with a number, `aws` or `ssm` and must be at most 100 characters",
+            false,
+        )
+        .add_context(parameter_name)
+        .add_context(&invalid_labels.join(",")));
+    }
+
+    Ok(aws_output.parameter_version)
+}
+
+pub async fn unlabel_parameter_version(parameter_name: &str, version: i64, labels: &[&str]) -> Result<(), FluffError> {
+    let client = FluffContext::shared().await.ssm();
+
+    client
+        .unlabel_parameter_version()
+        .name(parameter_name)
+        .parameter_version(version)
+        .set_labels(Some(labels.iter().map(|label| String::from(*label)).collect()))
+        .send()
+        .await
+        .map_err(|err| parameter_error(parameter_name, "Unable to unlabel a parameter version", err))?;
+
+    ParameterCache::shared().invalidate(parameter_name);
+    Ok(())
+}
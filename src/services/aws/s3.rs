@@ -1,10 +1,136 @@
-use aws_config::BehaviorVersion;
+use std::collections::HashMap;
+
+use aws_sdk_s3::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_s3::primitives::{ByteStream, DateTime};
+use aws_sdk_s3::types::{Delete, ObjectIdentifier, ServerSideEncryption};
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
 
 use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+
+pub mod list;
+pub mod multipart;
+pub mod presign;
+pub mod store;
+pub mod stream;
+
+pub use list::{list_objects, list_objects_page, list_objects_stream, ListObjectsRequest, ObjectListing, ObjectSummary};
+pub use multipart::{upload_stream, MultipartOptions};
+pub use presign::{
+    presigned_get_url, presigned_post, presigned_put_url, PresignedPost, PresignedPostRequest, PresignedUrl,
+};
+pub use store::S3ObjectStore;
+pub use stream::{read_object_stream, ByteRange, ObjectReader};
+
+// DeleteObjects accepts at most 1000 keys per call
+const MAX_DELETE_KEYS: usize = 1000;
+
+#[derive(Debug, Clone, Default)]
+pub struct PutObjectOptions {
+    pub(crate) content_type: Option<String>,
+    pub(crate) cache_control: Option<String>,
+    pub(crate) metadata: HashMap<String, String>,
+    server_side_encryption: Option<ServerSideEncryption>,
+    kms_key_id: Option<String>,
+}
+
+impl PutObjectOptions {
+    pub fn new() -> PutObjectOptions {
+        PutObjectOptions::default()
+    }
+
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(String::from(content_type));
+        self
+    }
+
+    pub fn cache_control(mut self, cache_control: &str) -> Self {
+        self.cache_control = Some(String::from(cache_control));
+        self
+    }
+
+    // Stored as `x-amz-meta-<key>` headers, returned by `head_object`
+    pub fn metadata(mut self, key: &str, value: &str) -> Self {
+        self.metadata.insert(String::from(key), String::from(value));
+        self
+    }
+
+    pub fn server_side_encryption(mut self, encryption: ServerSideEncryption) -> Self {
+        self.server_side_encryption = Some(encryption);
+        self
+    }
+
+    // Encrypts the object with this KMS key instead of the bucket default
+    pub fn kms_key_id(mut self, kms_key_id: &str) -> Self {
+        self.server_side_encryption = Some(ServerSideEncryption::AwsKms);
+        self.kms_key_id = Some(String::from(kms_key_id));
+        self
+    }
+}
+
+// Maps a failed S3 call to the matching error, using the error code or the HTTP status
+// since HEAD responses have no body to carry a code
+pub(crate) fn s3_error<E>(bucket: &str, object_key: &str, description: &str, err: SdkError<E>) -> FluffError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+{
+    let status = err.raw_response().map(|response| response.status().as_u16());
+    let error = match (err.code(), status) {
+        (Some("NoSuchKey") | Some("NotFound"), _) | (None, Some(404)) => {
+            FluffError::new_u16(404, "S3ObjectNotFound", "S3 file does not exist", false)
+        }
+        (Some("NoSuchBucket"), _) => FluffError::new_u16(404, "S3BucketNotFound", "S3 bucket does not exist", false),
+        (Some("AccessDenied") | Some("Forbidden"), _) | (None, Some(403)) => {
+            FluffError::new_u16(403, "S3AccessDenied", "Access to the S3 file was denied", false)
+        }
+        (Some("PreconditionFailed"), _) | (None, Some(412)) => {
+            FluffError::new_u16(412, "S3PreconditionFailed", "The S3 file does not match the condition", false)
+        }
+        (Some("SlowDown") | Some("ThrottlingException") | Some("RequestLimitExceeded"), _) | (_, Some(429)) => {
+            FluffError::new_u16(429, "S3Throttled", "S3 request rate exceeded", true)
+        }
+        (_, Some(status)) if (400..500).contains(&status) && status != 408 => {
+            FluffError::new_u16(400, "S3InvalidRequest", description, false)
+        }
+        _ => FluffError::new_u16(500, "S3Error", description, true),
+    }
+    .add_context(bucket)
+    .add_context(object_key);
+    match err.message() {
+        Some(message) => error.add_context(message),
+        None => error.add_context(&DisplayErrorContext(&err).to_string()),
+    }
+}
+
+// The connection failed while the body was being read
+pub(crate) fn body_error(bucket: &str, object_key: &str, err: impl ToString) -> FluffError {
+    FluffError::new_u16(500, "S3Error", "S3 file cannot be read", true)
+        .add_context(bucket)
+        .add_context(object_key)
+        .add_context(&err.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectMetadata {
+    pub size: i64,
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime>,
+    pub content_type: Option<String>,
+    pub cache_control: Option<String>,
+    pub metadata: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct DeletedObjects {
+    pub deleted: Vec<String>,
+    // Keys that could not be deleted, with the reason given by S3
+    pub failed: Vec<(String, String)>,
+}
 
 pub async fn read_object(bucket: &str, object_key: &str) -> Result<Vec<u8>, crate::errors::FluffError> {
-    let config = aws_config::load_defaults(BehaviorVersion::latest()).await;
-    let client = aws_sdk_s3::Client::new(&config);
+    let client = FluffContext::shared().await.s3();
 
     let response = client
         .get_object()
@@ -12,18 +138,211 @@ pub async fn read_object(bucket: &str, object_key: &str) -> Result<Vec<u8>, crat
         .key(object_key)
         .send()
         .await
-        .map_err(|err| {
-            FluffError::new_u16(500, "S3accessdenied", "S3 file cannot be read", true)
-                .add_context(bucket)
-                .add_context(object_key)
-                .add_context(&err.to_string())
-        })?;
-
-    let data = response.body.collect().await.map_err(|err| {
-        FluffError::new_u16(500, "S3accessdenied", "S3 file cannot be read", true)
+        .map_err(|err| s3_error(bucket, object_key, "S3 file cannot be read", err))?;
+
+    let data = response
+        .body
+        .collect()
+        .await
+        .map_err(|err| body_error(bucket, object_key, err))?;
+    Ok(data.to_vec())
+}
+
+// Returns the ETag of the new object
+pub async fn put_object(
+    bucket: &str,
+    object_key: &str,
+    data: Vec<u8>,
+    options: &PutObjectOptions,
+) -> Result<Option<String>, FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    let response = client
+        .put_object()
+        .bucket(bucket)
+        .key(object_key)
+        .body(ByteStream::from(data))
+        .set_content_type(options.content_type.clone())
+        .set_cache_control(options.cache_control.clone())
+        .set_metadata(Some(options.metadata.clone()).filter(|metadata| !metadata.is_empty()))
+        .set_server_side_encryption(options.server_side_encryption.clone())
+        .set_ssekms_key_id(options.kms_key_id.clone())
+        .send()
+        .await
+        .map_err(|err| s3_error(bucket, object_key, "S3 file cannot be written", err))?;
+
+    Ok(response.e_tag)
+}
+
+pub async fn head_object(bucket: &str, object_key: &str) -> Result<ObjectMetadata, FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    let response = client
+        .head_object()
+        .bucket(bucket)
+        .key(object_key)
+        .send()
+        .await
+        .map_err(|err| s3_error(bucket, object_key, "S3 file cannot be read", err))?;
+
+    Ok(ObjectMetadata {
+        size: response.content_length.unwrap_or_default(),
+        etag: response.e_tag,
+        last_modified: response.last_modified,
+        content_type: response.content_type,
+        cache_control: response.cache_control,
+        metadata: response.metadata.unwrap_or_default(),
+    })
+}
+
+// Deleting a key that does not exist succeeds
+pub async fn delete_object(bucket: &str, object_key: &str) -> Result<(), FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(object_key)
+        .send()
+        .await
+        .map_err(|err| s3_error(bucket, object_key, "S3 file cannot be deleted", err))?;
+
+    Ok(())
+}
+
+pub async fn delete_objects(bucket: &str, object_keys: &[&str]) -> Result<DeletedObjects, FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    let mut result = DeletedObjects::default();
+    for chunk in object_keys.chunks(MAX_DELETE_KEYS) {
+        let objects = chunk
+            .iter()
+            .map(|key| ObjectIdentifier::builder().key(*key).build())
+            .collect::<Result<Vec<ObjectIdentifier>, _>>()
+            .and_then(|objects| Delete::builder().set_objects(Some(objects)).quiet(false).build())
+            .map_err(|err| {
+                FluffError::new_u16(400, "S3DeleteError", "S3 delete request is invalid", false)
+                    .add_context(bucket)
+                    .add_context(&err.to_string())
+            })?;
+
+        let response = client
+            .delete_objects()
+            .bucket(bucket)
+            .delete(objects)
+            .send()
+            .await
+            .map_err(|err| s3_error(bucket, &chunk.join(","), "S3 files cannot be deleted", err))?;
+
+        result
+            .deleted
+            .extend(response.deleted.unwrap_or_default().into_iter().filter_map(|deleted| deleted.key));
+        result.failed.extend(response.errors.unwrap_or_default().into_iter().filter_map(|error| {
+            let reason = error.message.or(error.code).unwrap_or_default();
+            error.key.map(|key| (key, reason))
+        }));
+    }
+
+    Ok(result)
+}
+
+pub async fn read_text(bucket: &str, object_key: &str) -> Result<String, FluffError> {
+    String::from_utf8(read_object(bucket, object_key).await?).map_err(|err| {
+        FluffError::new_u16(500, "S3DecodeError", "S3 file is not valid UTF-8 text", false)
+            .add_context(bucket)
+            .add_context(object_key)
+            .add_context(&err.to_string())
+    })
+}
+
+pub async fn read_json<T: DeserializeOwned>(bucket: &str, object_key: &str) -> Result<T, FluffError> {
+    serde_json::from_slice(&read_object(bucket, object_key).await?).map_err(|err| {
+        FluffError::new_u16(500, "S3DecodeError", "S3 file is not a valid JSON document for the requested type", false)
+            .add_context(bucket)
+            .add_context(object_key)
+            .add_context(&err.to_string())
+    })
+}
+
+// Stored as `application/json`, returns the ETag of the new object
+pub async fn write_json<T: Serialize + ?Sized>(
+    bucket: &str,
+    object_key: &str,
+    value: &T,
+) -> Result<Option<String>, FluffError> {
+    let data = serde_json::to_vec(value).map_err(|err| {
+        FluffError::new_u16(500, "S3EncodeError", "Value cannot be serialized as JSON", false)
             .add_context(bucket)
             .add_context(object_key)
             .add_context(&err.to_string())
     })?;
-    Ok(data.to_vec())
+    put_object(bucket, object_key, data, &PutObjectOptions::new().content_type("application/json")).await
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct ReadConditions {
+    if_none_match: Option<String>,
+    if_modified_since: Option<DateTime>,
+}
+
+impl ReadConditions {
+    pub fn new() -> ReadConditions {
+        ReadConditions::default()
+    }
+
+    // ETag of the copy held by the caller
+    pub fn if_none_match(mut self, etag: &str) -> Self {
+        self.if_none_match = Some(String::from(etag));
+        self
+    }
+
+    pub fn if_modified_since(mut self, last_modified: DateTime) -> Self {
+        self.if_modified_since = Some(last_modified);
+        self
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectContent {
+    pub data: Vec<u8>,
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime>,
+    pub content_type: Option<String>,
+}
+
+// Returns None when the object did not change since the copy described by the conditions
+pub async fn read_object_if_changed(
+    bucket: &str,
+    object_key: &str,
+    conditions: &ReadConditions,
+) -> Result<Option<ObjectContent>, FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    let response = client
+        .get_object()
+        .bucket(bucket)
+        .key(object_key)
+        .set_if_none_match(conditions.if_none_match.clone())
+        .set_if_modified_since(conditions.if_modified_since)
+        .send()
+        .await;
+    let response = match response {
+        Ok(response) => response,
+        Err(err) if err.raw_response().is_some_and(|response| response.status().as_u16() == 304) => {
+            return Ok(None)
+        }
+        Err(err) => return Err(s3_error(bucket, object_key, "S3 file cannot be read", err)),
+    };
+
+    let data = response
+        .body
+        .collect()
+        .await
+        .map_err(|err| body_error(bucket, object_key, err))?;
+    Ok(Some(ObjectContent {
+        data: data.to_vec(),
+        etag: response.e_tag,
+        last_modified: response.last_modified,
+        content_type: response.content_type,
+    }))
 }
@@ -0,0 +1,296 @@
+use aws_sdk_s3::primitives::DateTime;
+use futures::stream::{self, Stream, TryStreamExt};
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::s3::s3_error;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ObjectSummary {
+    pub key: String,
+    pub size: i64,
+    pub etag: Option<String>,
+    pub last_modified: Option<DateTime>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ObjectListing {
+    pub objects: Vec<ObjectSummary>,
+    // Groups of keys sharing a prefix up to the delimiter, e.g. the "folders" of a prefix
+    pub common_prefixes: Vec<String>,
+    // Token of the next page, None on the last page
+    pub next_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListObjectsRequest {
+    bucket: String,
+    prefix: Option<String>,
+    delimiter: Option<String>,
+    limit: Option<usize>,
+    page_size: Option<i32>,
+    continuation_token: Option<String>,
+}
+
+impl ListObjectsRequest {
+    pub fn new(bucket: &str) -> ListObjectsRequest {
+        ListObjectsRequest {
+            bucket: String::from(bucket),
+            prefix: None,
+            delimiter: None,
+            limit: None,
+            page_size: None,
+            continuation_token: None,
+        }
+    }
+
+    pub fn prefix(mut self, prefix: &str) -> Self {
+        self.prefix = Some(String::from(prefix));
+        self
+    }
+
+    // Keys containing the delimiter after the prefix are grouped in `common_prefixes`
+    pub fn delimiter(mut self, delimiter: &str) -> Self {
+        self.delimiter = Some(String::from(delimiter));
+        self
+    }
+
+    // Maximum number of keys returned across all pages, common prefixes count as keys like with S3.
+    // Listing with a limit of 0 fails with a 400 InvalidListLimit
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    // Maximum number of keys returned by S3 for each page, at most 1000
+    pub fn page_size(mut self, page_size: i32) -> Self {
+        self.page_size = Some(page_size);
+        self
+    }
+
+    // Resumes a listing after the last key of a previous one, from its `next_token`
+    pub fn continuation_token(mut self, token: &str) -> Self {
+        self.continuation_token = Some(String::from(token));
+        self
+    }
+
+    pub(crate) fn with_bucket(mut self, bucket: &str) -> Self {
+        self.bucket = String::from(bucket);
+        self
+    }
+
+    pub(crate) fn key_prefix(&self) -> &str {
+        self.prefix.as_deref().unwrap_or_default()
+    }
+
+    pub(crate) fn key_delimiter(&self) -> Option<&str> {
+        self.delimiter.as_deref()
+    }
+
+    pub(crate) fn item_limit(&self) -> Option<usize> {
+        self.limit
+    }
+
+    pub(crate) fn start_token(&self) -> Option<&str> {
+        self.continuation_token.as_deref()
+    }
+
+    // An empty page could not tell whether keys remain
+    pub(crate) fn validate(&self) -> Result<(), FluffError> {
+        if self.limit == Some(0) {
+            return Err(FluffError::new_u16(400, "InvalidListLimit", "The list limit must be at least 1", false)
+                .add_context(&self.bucket)
+                .add_context(self.key_prefix()));
+        }
+        Ok(())
+    }
+}
+
+// One page of at most `page_size` keys, starting at the continuation token of the request
+pub async fn list_objects_page(request: &ListObjectsRequest) -> Result<ObjectListing, FluffError> {
+    fetch_page(request, request.page_size, request.continuation_token.clone()).await
+}
+
+async fn fetch_page(
+    request: &ListObjectsRequest,
+    max_keys: Option<i32>,
+    continuation_token: Option<String>,
+) -> Result<ObjectListing, FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    let aws_output = client
+        .list_objects_v2()
+        .bucket(&request.bucket)
+        .set_prefix(request.prefix.clone())
+        .set_delimiter(request.delimiter.clone())
+        .set_max_keys(max_keys)
+        .set_continuation_token(continuation_token)
+        .send()
+        .await
+        .map_err(|err| {
+            let prefix = request.prefix.as_deref().unwrap_or_default();
+            s3_error(&request.bucket, prefix, "S3 objects cannot be listed", err)
+        })?;
+
+    Ok(ObjectListing {
+        objects: aws_output
+            .contents
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|object| {
+                Some(ObjectSummary {
+                    key: object.key?,
+                    size: object.size.unwrap_or_default(),
+                    etag: object.e_tag,
+                    last_modified: object.last_modified,
+                })
+            })
+            .collect(),
+        common_prefixes: aws_output
+            .common_prefixes
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|prefix| prefix.prefix)
+            .collect(),
+        next_token: aws_output.next_continuation_token.filter(|_| aws_output.is_truncated == Some(true)),
+    })
+}
+
+// Pages are never asked for more keys than the limit still allows, so a limited listing
+// stops on a page boundary and the token of the last page resumes right after it
+struct Pages {
+    request: ListObjectsRequest,
+    token: Option<String>,
+    remaining: Option<usize>,
+    done: bool,
+}
+
+impl Pages {
+    fn new(request: ListObjectsRequest) -> Pages {
+        Pages {
+            remaining: request.limit,
+            token: request.continuation_token.clone(),
+            request,
+            done: false,
+        }
+    }
+
+    fn is_done(&self) -> bool {
+        self.done || self.remaining == Some(0)
+    }
+
+    fn max_keys(&self) -> Option<i32> {
+        match self.remaining {
+            Some(remaining) => {
+                let remaining = i32::try_from(remaining).unwrap_or(i32::MAX);
+                Some(self.request.page_size.map_or(remaining, |page_size| page_size.min(remaining)))
+            }
+            None => self.request.page_size,
+        }
+    }
+
+    fn record(&mut self, page: &ObjectListing) {
+        let keys = page.objects.len() + page.common_prefixes.len();
+        self.remaining = self.remaining.map(|remaining| remaining.saturating_sub(keys));
+        self.done = page.next_token.is_none();
+        self.token = page.next_token.clone();
+    }
+
+    async fn next(&mut self) -> Result<Option<ObjectListing>, FluffError> {
+        self.request.validate()?;
+        if self.is_done() {
+            return Ok(None);
+        }
+        let page = fetch_page(&self.request, self.max_keys(), self.token.take()).await?;
+        self.record(&page);
+        Ok(Some(page))
+    }
+}
+
+pub fn list_objects_stream(request: ListObjectsRequest) -> impl Stream<Item = Result<ObjectSummary, FluffError>> {
+    stream::try_unfold(Pages::new(request), |mut pages| async move {
+        Ok::<_, FluffError>(pages.next().await?.map(|page| (page.objects, pages)))
+    })
+    .map_ok(|objects| stream::iter(objects.into_iter().map(Ok)))
+    .try_flatten()
+}
+
+// Reads every page up to the limit, `next_token` is set when more keys remain
+pub async fn list_objects(request: &ListObjectsRequest) -> Result<ObjectListing, FluffError> {
+    let mut listing = ObjectListing::default();
+    let mut pages = Pages::new(request.clone());
+
+    while let Some(page) = pages.next().await? {
+        listing.objects.extend(page.objects);
+        listing.common_prefixes.extend(page.common_prefixes);
+        listing.next_token = page.next_token;
+    }
+
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn page(objects: &[&str], common_prefixes: &[&str], next_token: Option<&str>) -> ObjectListing {
+        ObjectListing {
+            objects: objects
+                .iter()
+                .map(|key| ObjectSummary {
+                    key: String::from(*key),
+                    size: 0,
+                    etag: None,
+                    last_modified: None,
+                })
+                .collect(),
+            common_prefixes: common_prefixes.iter().map(|prefix| String::from(*prefix)).collect(),
+            next_token: next_token.map(String::from),
+        }
+    }
+
+    #[test]
+    fn pages_never_ask_for_more_than_the_limit() {
+        let mut pages = Pages::new(ListObjectsRequest::new("bucket").limit(5).page_size(2));
+        assert_eq!(pages.max_keys(), Some(2));
+
+        pages.record(&page(&["a", "b"], &[], Some("t1")));
+        assert_eq!((pages.max_keys(), pages.token.as_deref()), (Some(2), Some("t1")));
+
+        // Common prefixes count as keys, like with S3
+        pages.record(&page(&["c"], &["d/"], Some("t2")));
+        assert_eq!(pages.max_keys(), Some(1));
+
+        pages.record(&page(&["e"], &[], Some("t3")));
+        assert!(pages.is_done());
+        assert_eq!(pages.token.as_deref(), Some("t3"));
+    }
+
+    #[test]
+    fn pages_without_limit_stop_on_the_last_page() {
+        let mut pages = Pages::new(ListObjectsRequest::new("bucket"));
+        assert_eq!(pages.max_keys(), None);
+
+        pages.record(&page(&["a"], &[], Some("t1")));
+        assert!(!pages.is_done());
+        pages.record(&page(&["b"], &[], None));
+        assert!(pages.is_done());
+    }
+
+    #[test]
+    fn pages_start_at_the_request_token() {
+        let pages = Pages::new(ListObjectsRequest::new("bucket").limit(3000).continuation_token("t1"));
+
+        assert_eq!(pages.token.as_deref(), Some("t1"));
+        assert_eq!(pages.max_keys(), Some(3000));
+    }
+
+    #[test]
+    fn zero_limit_is_rejected() {
+        let err = ListObjectsRequest::new("bucket").limit(0).validate().unwrap_err();
+
+        assert_eq!(err.http_code, 400);
+        assert_eq!(err.error_name, "InvalidListLimit");
+        assert!(ListObjectsRequest::new("bucket").limit(1).validate().is_ok());
+    }
+}
@@ -0,0 +1,208 @@
+use aws_sdk_s3::primitives::ByteStream;
+use aws_sdk_s3::types::{CompletedMultipartUpload, CompletedPart};
+use bytes::{Bytes, BytesMut};
+use futures::stream::{FuturesUnordered, Stream, StreamExt, TryStreamExt};
+use futures::{select, FutureExt};
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::s3::{put_object, s3_error, PutObjectOptions};
+
+// S3 rejects parts smaller than 5 MiB, except for the last one
+const MIN_PART_SIZE: usize = 5 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct MultipartOptions {
+    part_size: usize,
+    concurrency: usize,
+    object: PutObjectOptions,
+}
+
+impl Default for MultipartOptions {
+    fn default() -> Self {
+        MultipartOptions {
+            part_size: 8 * 1024 * 1024,
+            concurrency: 4,
+            object: PutObjectOptions::default(),
+        }
+    }
+}
+
+impl MultipartOptions {
+    pub fn new() -> MultipartOptions {
+        MultipartOptions::default()
+    }
+
+    // Raised to 5 MiB if smaller, each part in flight is held in memory
+    pub fn part_size(mut self, part_size: usize) -> Self {
+        self.part_size = part_size.max(MIN_PART_SIZE);
+        self
+    }
+
+    // Number of parts uploaded at the same time
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    // Content type, metadata and encryption of the uploaded object
+    pub fn object_options(mut self, object: PutObjectOptions) -> Self {
+        self.object = object;
+        self
+    }
+}
+
+// Uploads a stream of unknown length, returns the ETag of the new object.
+// Streams shorter than one part are sent with a single PutObject, otherwise a multipart upload
+// is used and aborted if the stream or any part fails, so no orphan parts are billed.
+pub async fn upload_stream<S>(
+    bucket: &str,
+    object_key: &str,
+    data: S,
+    options: &MultipartOptions,
+) -> Result<Option<String>, FluffError>
+where
+    S: Stream<Item = Result<Bytes, FluffError>>,
+{
+    let mut data = Box::pin(data);
+    let mut buffer = BytesMut::new();
+
+    while buffer.len() < options.part_size {
+        match data.try_next().await? {
+            Some(chunk) => buffer.extend_from_slice(&chunk),
+            None => return put_object(bucket, object_key, buffer.to_vec(), &options.object).await,
+        }
+    }
+
+    let client = FluffContext::shared().await.s3();
+    let object = &options.object;
+    let upload = client
+        .create_multipart_upload()
+        .bucket(bucket)
+        .key(object_key)
+        .set_content_type(object.content_type.clone())
+        .set_cache_control(object.cache_control.clone())
+        .set_metadata(Some(object.metadata.clone()).filter(|metadata| !metadata.is_empty()))
+        .set_server_side_encryption(object.server_side_encryption.clone())
+        .set_ssekms_key_id(object.kms_key_id.clone())
+        .send()
+        .await
+        .map_err(|err| s3_error(bucket, object_key, "S3 file cannot be written", err))?;
+    let upload_id = upload.upload_id.ok_or_else(|| {
+        FluffError::new_u16(500, "S3Error", "S3 did not return an upload id", true)
+            .add_context(bucket)
+            .add_context(object_key)
+    })?;
+
+    match upload_parts(bucket, object_key, &upload_id, data, buffer, options).await {
+        Ok(parts) => {
+            let response = client
+                .complete_multipart_upload()
+                .bucket(bucket)
+                .key(object_key)
+                .upload_id(&upload_id)
+                .multipart_upload(CompletedMultipartUpload::builder().set_parts(Some(parts)).build())
+                .send()
+                .await;
+            match response {
+                Ok(response) => Ok(response.e_tag),
+                Err(err) => {
+                    abort_upload(bucket, object_key, &upload_id).await;
+                    Err(s3_error(bucket, object_key, "S3 file cannot be written", err))
+                }
+            }
+        }
+        Err(err) => {
+            abort_upload(bucket, object_key, &upload_id).await;
+            Err(err)
+        }
+    }
+}
+
+async fn abort_upload(bucket: &str, object_key: &str, upload_id: &str) {
+    let client = FluffContext::shared().await.s3();
+    // The upload already failed, a lifecycle rule removes the parts if the abort fails too
+    let _ = client
+        .abort_multipart_upload()
+        .bucket(bucket)
+        .key(object_key)
+        .upload_id(upload_id)
+        .send()
+        .await;
+}
+
+async fn upload_part(
+    bucket: &str,
+    object_key: &str,
+    upload_id: &str,
+    part_number: i32,
+    part: Bytes,
+) -> Result<CompletedPart, FluffError> {
+    let client = FluffContext::shared().await.s3();
+
+    let response = client
+        .upload_part()
+        .bucket(bucket)
+        .key(object_key)
+        .upload_id(upload_id)
+        .part_number(part_number)
+        .body(ByteStream::from(part))
+        .send()
+        .await
+        .map_err(|err| {
+            s3_error(bucket, object_key, "S3 file cannot be written", err).add_context(&format!("part {}", part_number))
+        })?;
+
+    Ok(CompletedPart::builder()
+        .part_number(part_number)
+        .set_e_tag(response.e_tag)
+        .build())
+}
+
+async fn upload_parts<S>(
+    bucket: &str,
+    object_key: &str,
+    upload_id: &str,
+    mut data: S,
+    mut buffer: BytesMut,
+    options: &MultipartOptions,
+) -> Result<Vec<CompletedPart>, FluffError>
+where
+    S: Stream<Item = Result<Bytes, FluffError>> + Unpin,
+{
+    let mut in_flight = FuturesUnordered::new();
+    let mut parts = Vec::new();
+    let mut part_number = 0;
+    let mut exhausted = false;
+
+    loop {
+        while in_flight.len() < options.concurrency
+            && (buffer.len() >= options.part_size || (exhausted && !buffer.is_empty()))
+        {
+            part_number += 1;
+            let part = buffer.split_to(buffer.len().min(options.part_size)).freeze();
+            in_flight.push(upload_part(bucket, object_key, upload_id, part_number, part));
+        }
+
+        if exhausted || buffer.len() >= options.part_size {
+            // Nothing more to read until a part slot is free
+            match in_flight.next().await {
+                Some(part) => parts.push(part?),
+                None => break,
+            }
+            continue;
+        }
+
+        // The next part is read while the previous ones are uploaded
+        select! {
+            chunk = data.try_next().fuse() => match chunk? {
+                Some(chunk) => buffer.extend_from_slice(&chunk),
+                None => exhausted = true,
+            },
+            part = in_flight.select_next_some() => parts.push(part?),
+        }
+    }
+
+    parts.sort_by_key(|part| part.part_number);
+    Ok(parts)
+}
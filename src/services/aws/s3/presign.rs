@@ -0,0 +1,333 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use aws_sdk_s3::config::{Credentials, ProvideCredentials};
+use aws_sdk_s3::presigning::{PresignedRequest, PresigningConfig};
+use aws_sdk_s3::primitives::{DateTime, DateTimeFormat};
+use aws_sigv4::sign::v4::{calculate_signature, generate_signing_key};
+use serde::Serialize;
+use serde_json::json;
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+
+// A URL the client calls directly, with the headers it must send
+#[derive(Debug, Clone, Serialize)]
+pub struct PresignedUrl {
+    pub url: String,
+    pub method: String,
+    pub headers: BTreeMap<String, String>,
+    // UNIX timestamp in seconds
+    pub expires_at: u64,
+}
+
+// A browser form upload, `fields` must be sent as form fields before the `file` field
+#[derive(Debug, Clone, Serialize)]
+pub struct PresignedPost {
+    pub url: String,
+    pub fields: BTreeMap<String, String>,
+    // UNIX timestamp in seconds
+    pub expires_at: u64,
+}
+
+fn to_json<T: Serialize>(value: &T) -> Result<String, FluffError> {
+    serde_json::to_string(value).map_err(|err| {
+        FluffError::new_u16(500, "PresignSerializationError", "Presigned request cannot be serialized", false)
+            .add_context(&err.to_string())
+    })
+}
+
+impl PresignedUrl {
+    // Body for `common_responses::ok_200_json`
+    pub fn to_json(&self) -> Result<String, FluffError> {
+        to_json(self)
+    }
+
+    fn from_request(request: PresignedRequest, expires_at: u64) -> PresignedUrl {
+        PresignedUrl {
+            url: String::from(request.uri()),
+            method: String::from(request.method()),
+            headers: request
+                .headers()
+                .map(|(name, value)| (String::from(name), String::from(value)))
+                .collect(),
+            expires_at,
+        }
+    }
+}
+
+impl PresignedPost {
+    // Body for `common_responses::ok_200_json`
+    pub fn to_json(&self) -> Result<String, FluffError> {
+        to_json(self)
+    }
+}
+
+fn presigning_config(expires_in: Duration) -> Result<(PresigningConfig, u64), FluffError> {
+    let start = SystemTime::now();
+    let config = PresigningConfig::builder()
+        .start_time(start)
+        .expires_in(expires_in)
+        .build()
+        .map_err(|err| {
+            FluffError::new_u16(400, "InvalidPresignExpiry", "Presigned URLs expire after at most 7 days", false)
+                .add_context(&err.to_string())
+        })?;
+    Ok((config, unix_seconds(start + expires_in)))
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or_default()
+}
+
+fn presign_error(bucket: &str, object_key: &str, err: impl ToString) -> FluffError {
+    FluffError::new_u16(500, "S3PresignError", "S3 request cannot be presigned", true)
+        .add_context(bucket)
+        .add_context(object_key)
+        .add_context(&err.to_string())
+}
+
+pub async fn presigned_get_url(bucket: &str, object_key: &str, expires_in: Duration) -> Result<PresignedUrl, FluffError> {
+    let client = FluffContext::shared().await.s3();
+    let (config, expires_at) = presigning_config(expires_in)?;
+
+    let request = client
+        .get_object()
+        .bucket(bucket)
+        .key(object_key)
+        .presigned(config)
+        .await
+        .map_err(|err| presign_error(bucket, object_key, err))?;
+
+    Ok(PresignedUrl::from_request(request, expires_at))
+}
+
+// With a content type, the upload must send the same `content-type` header
+pub async fn presigned_put_url(
+    bucket: &str,
+    object_key: &str,
+    expires_in: Duration,
+    content_type: Option<&str>,
+) -> Result<PresignedUrl, FluffError> {
+    let client = FluffContext::shared().await.s3();
+    let (config, expires_at) = presigning_config(expires_in)?;
+
+    let request = client
+        .put_object()
+        .bucket(bucket)
+        .key(object_key)
+        .set_content_type(content_type.map(String::from))
+        .presigned(config)
+        .await
+        .map_err(|err| presign_error(bucket, object_key, err))?;
+
+    Ok(PresignedUrl::from_request(request, expires_at))
+}
+
+#[derive(Debug, Clone)]
+enum ContentTypeRule {
+    Equals(String),
+    StartsWith(String),
+}
+
+#[derive(Debug, Clone)]
+pub struct PresignedPostRequest {
+    bucket: String,
+    object_key: String,
+    expires_in: Duration,
+    content_length: Option<(u64, u64)>,
+    content_type: Option<ContentTypeRule>,
+}
+
+impl PresignedPostRequest {
+    pub fn new(bucket: &str, object_key: &str, expires_in: Duration) -> PresignedPostRequest {
+        PresignedPostRequest {
+            bucket: String::from(bucket),
+            object_key: String::from(object_key),
+            expires_in,
+            content_length: None,
+            content_type: None,
+        }
+    }
+
+    // Size of the uploaded file in bytes, bounds included
+    pub fn content_length(mut self, min: u64, max: u64) -> Self {
+        self.content_length = Some((min, max));
+        self
+    }
+
+    pub fn content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(ContentTypeRule::Equals(String::from(content_type)));
+        self
+    }
+
+    // e.g. `image/` to accept any image, the form must then set its own `Content-Type` field
+    pub fn content_type_prefix(mut self, prefix: &str) -> Self {
+        self.content_type = Some(ContentTypeRule::StartsWith(String::from(prefix)));
+        self
+    }
+}
+
+pub async fn presigned_post(request: &PresignedPostRequest) -> Result<PresignedPost, FluffError> {
+    // Same limit of 7 days as the presigned URLs
+    presigning_config(request.expires_in)?;
+
+    let context = FluffContext::shared().await;
+    let config = context.sdk_config();
+
+    let region = config
+        .region()
+        .map(|region| region.to_string())
+        .ok_or_else(|| presign_error(&request.bucket, &request.object_key, "no region configured"))?;
+    let credentials = match config.credentials_provider() {
+        Some(provider) => provider
+            .provide_credentials()
+            .await
+            .map_err(|err| presign_error(&request.bucket, &request.object_key, err))?,
+        None => return Err(presign_error(&request.bucket, &request.object_key, "no credentials configured")),
+    };
+
+    let url = match context.s3_endpoint_url() {
+        Some(endpoint) => format!("{}/{}", endpoint.trim_end_matches('/'), request.bucket),
+        None => format!("https://{}.s3.{}.amazonaws.com", request.bucket, region),
+    };
+
+    sign_post(request, url, &region, &credentials, SystemTime::now())
+}
+
+// Builds the policy of the form and signs it with SigV4
+fn sign_post(
+    request: &PresignedPostRequest,
+    url: String,
+    region: &str,
+    credentials: &Credentials,
+    now: SystemTime,
+) -> Result<PresignedPost, FluffError> {
+    let expires_at = now + request.expires_in;
+    let date_time = format_date_time(now)?;
+    let date = &date_time[..8];
+    let credential = format!("{}/{}/{}/s3/aws4_request", credentials.access_key_id(), date, region);
+
+    let mut fields = BTreeMap::new();
+    fields.insert(String::from("key"), request.object_key.clone());
+    fields.insert(String::from("x-amz-algorithm"), String::from("AWS4-HMAC-SHA256"));
+    fields.insert(String::from("x-amz-credential"), credential);
+    fields.insert(String::from("x-amz-date"), date_time.clone());
+    if let Some(token) = credentials.session_token() {
+        fields.insert(String::from("x-amz-security-token"), String::from(token));
+    }
+
+    let mut conditions = vec![json!({ "bucket": request.bucket })];
+    for (name, value) in &fields {
+        conditions.push(json!({ name: value }));
+    }
+    match &request.content_type {
+        Some(ContentTypeRule::Equals(content_type)) => {
+            fields.insert(String::from("Content-Type"), content_type.clone());
+            conditions.push(json!({ "Content-Type": content_type }));
+        }
+        Some(ContentTypeRule::StartsWith(prefix)) => {
+            conditions.push(json!(["starts-with", "$Content-Type", prefix]));
+        }
+        None => {}
+    }
+    if let Some((min, max)) = request.content_length {
+        conditions.push(json!(["content-length-range", min, max]));
+    }
+
+    let expiration = DateTime::from(expires_at)
+        .fmt(DateTimeFormat::DateTime)
+        .map_err(|err| presign_error(&request.bucket, &request.object_key, err))?;
+    let policy = json!({ "expiration": expiration, "conditions": conditions }).to_string();
+    let policy = aws_smithy_types::base64::encode(policy);
+
+    let signing_key = generate_signing_key(credentials.secret_access_key(), now, region, "s3");
+    fields.insert(String::from("x-amz-signature"), calculate_signature(signing_key, policy.as_bytes()));
+    fields.insert(String::from("policy"), policy);
+
+    Ok(PresignedPost {
+        url,
+        fields,
+        expires_at: unix_seconds(expires_at),
+    })
+}
+
+// `20240615T120000Z`, the format of `x-amz-date`
+fn format_date_time(time: SystemTime) -> Result<String, FluffError> {
+    let seconds = DateTime::from_secs(unix_seconds(time) as i64);
+    let formatted = seconds.fmt(DateTimeFormat::DateTime).map_err(|err| {
+        FluffError::new_u16(500, "S3PresignError", "S3 request cannot be presigned", true)
+            .add_context(&err.to_string())
+    })?;
+    Ok(formatted.replace(['-', ':'], ""))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::Value;
+
+    use super::*;
+
+    // 2024-06-15T12:00:00Z
+    const NOW: u64 = 1_718_452_800;
+
+    fn post(request: &PresignedPostRequest, session_token: Option<&str>) -> PresignedPost {
+        let credentials = Credentials::new("AKID", "secret", session_token.map(String::from), None, "test");
+        let now = UNIX_EPOCH + Duration::from_secs(NOW);
+        sign_post(request, String::from("https://bucket.s3.eu-west-1.amazonaws.com"), "eu-west-1", &credentials, now)
+            .unwrap()
+    }
+
+    fn policy(post: &PresignedPost) -> Value {
+        let policy = aws_smithy_types::base64::decode(&post.fields["policy"]).unwrap();
+        serde_json::from_slice(&policy).unwrap()
+    }
+
+    #[test]
+    fn fields_are_signed_for_the_date() {
+        let request = PresignedPostRequest::new("bucket", "uploads/a.png", Duration::from_secs(600));
+        let post = post(&request, None);
+
+        assert_eq!(post.fields["x-amz-date"], "20240615T120000Z");
+        assert_eq!(post.fields["x-amz-credential"], "AKID/20240615/eu-west-1/s3/aws4_request");
+        assert_eq!(post.fields["key"], "uploads/a.png");
+        assert_eq!(post.fields["x-amz-signature"].len(), 64);
+        assert!(!post.fields.contains_key("x-amz-security-token"));
+        assert_eq!(post.expires_at, NOW + 600);
+        assert_eq!(policy(&post)["expiration"], "2024-06-15T12:10:00Z");
+    }
+
+    #[test]
+    fn policy_contains_the_conditions() {
+        let request = PresignedPostRequest::new("bucket", "uploads/a.png", Duration::from_secs(600))
+            .content_length(1, 1024)
+            .content_type_prefix("image/");
+        let post = post(&request, Some("token"));
+        let conditions = policy(&post)["conditions"].as_array().unwrap().clone();
+
+        assert_eq!(post.fields["x-amz-security-token"], "token");
+        assert!(conditions.contains(&json!({ "bucket": "bucket" })));
+        assert!(conditions.contains(&json!({ "x-amz-security-token": "token" })));
+        assert!(conditions.contains(&json!(["starts-with", "$Content-Type", "image/"])));
+        assert!(conditions.contains(&json!(["content-length-range", 1, 1024])));
+        assert!(!post.fields.contains_key("Content-Type"));
+    }
+
+    #[test]
+    fn exact_content_type_is_a_field() {
+        let request = PresignedPostRequest::new("bucket", "a.txt", Duration::from_secs(60)).content_type("text/plain");
+        let post = post(&request, None);
+
+        assert_eq!(post.fields["Content-Type"], "text/plain");
+        assert!(policy(&post)["conditions"].as_array().unwrap().contains(&json!({ "Content-Type": "text/plain" })));
+    }
+
+    #[test]
+    fn expiry_is_limited_to_7_days() {
+        let request = PresignedPostRequest::new("bucket", "a.txt", Duration::from_secs(8 * 24 * 3600));
+        let err = futures::executor::block_on(presigned_post(&request)).unwrap_err();
+
+        assert_eq!(err.error_name, "InvalidPresignExpiry");
+        assert!(presigning_config(Duration::from_secs(7 * 24 * 3600)).is_ok());
+    }
+}
@@ -0,0 +1,41 @@
+use crate::errors::FluffError;
+use crate::services::aws::s3::{self, ListObjectsRequest, ObjectListing, ObjectMetadata, PutObjectOptions};
+use crate::services::object_store::ObjectStore;
+
+pub struct S3ObjectStore {
+    bucket: String,
+}
+
+impl S3ObjectStore {
+    pub fn new(bucket: &str) -> S3ObjectStore {
+        S3ObjectStore {
+            bucket: String::from(bucket),
+        }
+    }
+
+    pub fn bucket(&self) -> &str {
+        &self.bucket
+    }
+}
+
+impl ObjectStore for S3ObjectStore {
+    async fn get(&self, object_key: &str) -> Result<Vec<u8>, FluffError> {
+        s3::read_object(&self.bucket, object_key).await
+    }
+
+    async fn put(&self, object_key: &str, data: Vec<u8>, options: &PutObjectOptions) -> Result<Option<String>, FluffError> {
+        s3::put_object(&self.bucket, object_key, data, options).await
+    }
+
+    async fn delete(&self, object_key: &str) -> Result<(), FluffError> {
+        s3::delete_object(&self.bucket, object_key).await
+    }
+
+    async fn head(&self, object_key: &str) -> Result<ObjectMetadata, FluffError> {
+        s3::head_object(&self.bucket, object_key).await
+    }
+
+    async fn list(&self, request: &ListObjectsRequest) -> Result<ObjectListing, FluffError> {
+        s3::list_objects(&request.clone().with_bucket(&self.bucket)).await
+    }
+}
@@ -0,0 +1,121 @@
+use aws_sdk_s3::primitives::ByteStream;
+use bytes::Bytes;
+use futures::stream::{self, Stream};
+use tokio::io::AsyncBufRead;
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+use crate::services::aws::s3::{body_error, s3_error};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteRange {
+    // From this offset to the end of the object
+    From(u64),
+    // Both offsets included
+    Between(u64, u64),
+    // The last bytes of the object
+    Last(u64),
+}
+
+impl ByteRange {
+    fn header(&self) -> Result<String, FluffError> {
+        match self {
+            ByteRange::From(start) => Ok(format!("bytes={}-", start)),
+            ByteRange::Between(start, end) if end < start => Err(FluffError::new_u16(
+                400,
+                "InvalidRange",
+                "The end of a byte range cannot be before its start",
+                false,
+            )
+            .add_context(&format!("{}-{}", start, end))),
+            ByteRange::Between(start, end) => Ok(format!("bytes={}-{}", start, end)),
+            ByteRange::Last(length) => Ok(format!("bytes=-{}", length)),
+        }
+    }
+}
+
+// The body of an object, read from S3 as it is consumed
+pub struct ObjectReader {
+    pub bucket: String,
+    pub object_key: String,
+    // Length of the returned bytes, which is the length of the range when one was requested
+    pub content_length: i64,
+    pub content_range: Option<String>,
+    pub content_type: Option<String>,
+    pub etag: Option<String>,
+    body: ByteStream,
+}
+
+impl ObjectReader {
+    pub fn into_stream(self) -> impl Stream<Item = Result<Bytes, FluffError>> {
+        let bucket = self.bucket;
+        let object_key = self.object_key;
+        stream::try_unfold(self.body, move |mut body| {
+            let bucket = bucket.clone();
+            let object_key = object_key.clone();
+            async move {
+                match body.next().await {
+                    Some(Ok(chunk)) => Ok(Some((chunk, body))),
+                    Some(Err(err)) => Err(body_error(&bucket, &object_key, err)),
+                    None => Ok(None),
+                }
+            }
+        })
+    }
+
+    pub fn into_async_read(self) -> impl AsyncBufRead {
+        self.body.into_async_read()
+    }
+}
+
+// Starts reading an object without buffering it, the whole object is read when `range` is None
+pub async fn read_object_stream(
+    bucket: &str,
+    object_key: &str,
+    range: Option<ByteRange>,
+) -> Result<ObjectReader, FluffError> {
+    let client = FluffContext::shared().await.s3();
+    let range = range
+        .map(|range| range.header())
+        .transpose()
+        .map_err(|err| err.add_context(bucket).add_context(object_key))?;
+
+    let response = client
+        .get_object()
+        .bucket(bucket)
+        .key(object_key)
+        .set_range(range)
+        .send()
+        .await
+        .map_err(|err| s3_error(bucket, object_key, "S3 file cannot be read", err))?;
+
+    Ok(ObjectReader {
+        bucket: String::from(bucket),
+        object_key: String::from(object_key),
+        content_length: response.content_length.unwrap_or_default(),
+        content_range: response.content_range,
+        content_type: response.content_type,
+        etag: response.e_tag,
+        body: response.body,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn range_headers() {
+        assert_eq!(ByteRange::From(10).header().unwrap(), "bytes=10-");
+        assert_eq!(ByteRange::Between(0, 0).header().unwrap(), "bytes=0-0");
+        assert_eq!(ByteRange::Between(5, 9).header().unwrap(), "bytes=5-9");
+        assert_eq!(ByteRange::Last(20).header().unwrap(), "bytes=-20");
+    }
+
+    #[test]
+    fn reversed_range_is_rejected() {
+        let err = ByteRange::Between(9, 5).header().unwrap_err();
+        assert_eq!(err.http_code, 400);
+        assert_eq!(err.error_name, "InvalidRange");
+    }
+}
@@ -0,0 +1,250 @@
+use aws_sdk_secretsmanager::error::{DisplayErrorContext, ProvideErrorMetadata, SdkError};
+use aws_sdk_secretsmanager::primitives::{Blob, DateTime};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::FluffError;
+use crate::services::aws::context::FluffContext;
+
+pub mod cache;
+
+pub use cache::{get_cached_secret, get_cached_secret_json, get_cached_secret_string, SecretCache};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SecretValue {
+    String(String),
+    Binary(Vec<u8>),
+}
+
+impl SecretValue {
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            SecretValue::String(value) => value.as_bytes(),
+            SecretValue::Binary(value) => value,
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        match self {
+            SecretValue::String(value) => value.into_bytes(),
+            SecretValue::Binary(value) => value,
+        }
+    }
+}
+
+// One version of a secret with its metadata
+#[derive(Debug, Clone, PartialEq)]
+pub struct Secret {
+    pub name: String,
+    pub arn: Option<String>,
+    pub version_id: Option<String>,
+    pub version_stages: Vec<String>,
+    pub created: Option<DateTime>,
+    pub value: SecretValue,
+}
+
+impl Secret {
+    pub fn into_string(self) -> Result<String, FluffError> {
+        match self.value {
+            SecretValue::String(value) => Ok(value),
+            SecretValue::Binary(_) => Err(FluffError::new_u16(
+                500,
+                "SecretNotString",
+                "The secret holds a binary value",
+                false,
+            )
+            .add_context(&self.name)),
+        }
+    }
+
+    pub fn into_json<T: DeserializeOwned>(self) -> Result<T, FluffError> {
+        let name = self.name.clone();
+        serde_json::from_str(&self.into_string()?).map_err(|err| {
+            FluffError::new_u16(
+                500,
+                "SecretDeserializationError",
+                "The secret is not a valid JSON document for the requested type",
+                false,
+            )
+            .add_context(&name)
+            .add_context(&err.to_string())
+        })
+    }
+}
+
+// Maps the AWS error code of a failed call to the matching error
+pub(crate) fn secret_error<E, R>(secret_id: &str, description: &str, err: SdkError<E, R>) -> FluffError
+where
+    E: ProvideErrorMetadata + std::error::Error + 'static,
+    R: std::fmt::Debug,
+{
+    let error = match err.code() {
+        Some("ResourceNotFoundException") => FluffError::new_u16(
+            404,
+            "SecretNotFound",
+            "The requested secret or secret version was not found",
+            false,
+        ),
+        Some("ResourceExistsException") => FluffError::new_u16(
+            409,
+            "SecretAlreadyExists",
+            "A secret with this name already exists",
+            false,
+        ),
+        Some("InvalidParameterException") | Some("InvalidRequestException") | Some("LimitExceededException") => {
+            FluffError::new_u16(400, "InvalidSecretRequest", "The Secrets Manager request is invalid", false)
+        }
+        Some("DecryptionFailure") | Some("EncryptionFailure") => FluffError::new_u16(
+            500,
+            "SecretEncryptionError",
+            "The secret cannot be encrypted or decrypted with its KMS key",
+            false,
+        ),
+        Some("ThrottlingException") => FluffError::new_u16(
+            429,
+            "SecretsManagerThrottled",
+            "Secrets Manager request rate exceeded",
+            true,
+        ),
+        Some("AccessDeniedException") => FluffError::new_u16(
+            403,
+            "SecretsManagerAccessDenied",
+            "Access to Secrets Manager was denied",
+            false,
+        ),
+        _ => FluffError::new_u16(500, "SecretsManagerError", description, true),
+    }
+    .add_context(secret_id);
+    match err.message() {
+        Some(message) => error.add_context(message),
+        None => error.add_context(&DisplayErrorContext(&err).to_string()),
+    }
+}
+
+// Reads the version of a secret holding `version_stage`, AWSCURRENT when None
+pub async fn get_secret(secret_id: &str, version_stage: Option<&str>) -> Result<Secret, FluffError> {
+    let client = FluffContext::shared().await.secrets_manager();
+
+    let aws_output = client
+        .get_secret_value()
+        .secret_id(secret_id)
+        .set_version_stage(version_stage.map(String::from))
+        .send()
+        .await
+        .map_err(|err| secret_error(secret_id, "Unable to read Secrets Manager", err))?;
+
+    let value = match (aws_output.secret_string, aws_output.secret_binary) {
+        (Some(value), _) => SecretValue::String(value),
+        (None, Some(value)) => SecretValue::Binary(value.into_inner()),
+        (None, None) => {
+            return Err(FluffError::new_u16(
+                404,
+                "SecretNotFound",
+                "The requested secret or secret version was not found",
+                false,
+            )
+            .add_context(secret_id))
+        }
+    };
+
+    Ok(Secret {
+        name: aws_output.name.unwrap_or_else(|| String::from(secret_id)),
+        arn: aws_output.arn,
+        version_id: aws_output.version_id,
+        version_stages: aws_output.version_stages.unwrap_or_default(),
+        created: aws_output.created_date,
+        value,
+    })
+}
+
+// `version_stage` defaults to AWSCURRENT, e.g. AWSPREVIOUS reads the value before the last rotation
+pub async fn get_secret_string(secret_id: &str, version_stage: Option<&str>) -> Result<String, FluffError> {
+    get_secret(secret_id, version_stage).await?.into_string()
+}
+
+// String secrets are returned as their UTF-8 bytes, e.g. PEM keys
+pub async fn get_secret_binary(secret_id: &str, version_stage: Option<&str>) -> Result<Vec<u8>, FluffError> {
+    Ok(get_secret(secret_id, version_stage).await?.value.into_bytes())
+}
+
+pub async fn get_secret_json<T: DeserializeOwned>(secret_id: &str, version_stage: Option<&str>) -> Result<T, FluffError> {
+    get_secret(secret_id, version_stage).await?.into_json()
+}
+
+pub async fn create_secret(
+    name: &str,
+    value: SecretValue,
+    description: Option<&str>,
+) -> Result<String, FluffError> {
+    let client = FluffContext::shared().await.secrets_manager();
+
+    let request = client
+        .create_secret()
+        .name(name)
+        .set_description(description.map(String::from));
+    let request = match value {
+        SecretValue::String(value) => request.secret_string(value),
+        SecretValue::Binary(value) => request.secret_binary(Blob::new(value)),
+    };
+
+    let aws_output = request
+        .send()
+        .await
+        .map_err(|err| secret_error(name, "Unable to create a secret", err))?;
+
+    SecretCache::shared().invalidate(name);
+    Ok(aws_output.arn.unwrap_or_else(|| String::from(name)))
+}
+
+// Writes a new version of an existing secret, staged as AWSCURRENT when `version_stages` is empty.
+// Returns the id of the new version.
+pub async fn put_secret(
+    secret_id: &str,
+    value: SecretValue,
+    version_stages: &[&str],
+) -> Result<String, FluffError> {
+    let client = FluffContext::shared().await.secrets_manager();
+
+    let stages = match version_stages.is_empty() {
+        true => None,
+        false => Some(version_stages.iter().map(|stage| String::from(*stage)).collect()),
+    };
+    let request = client
+        .put_secret_value()
+        .secret_id(secret_id)
+        .set_version_stages(stages);
+    let request = match value {
+        SecretValue::String(value) => request.secret_string(value),
+        SecretValue::Binary(value) => request.secret_binary(Blob::new(value)),
+    };
+
+    let aws_output = request
+        .send()
+        .await
+        .map_err(|err| secret_error(secret_id, "Unable to write in Secrets Manager", err))?;
+
+    SecretCache::shared().invalidate(secret_id);
+    Ok(aws_output.version_id.unwrap_or_default())
+}
+
+pub async fn put_secret_string(secret_id: &str, value: &str) -> Result<String, FluffError> {
+    put_secret(secret_id, SecretValue::String(String::from(value)), &[]).await
+}
+
+pub async fn put_secret_binary(secret_id: &str, value: Vec<u8>) -> Result<String, FluffError> {
+    put_secret(secret_id, SecretValue::Binary(value), &[]).await
+}
+
+pub async fn put_secret_json<T: Serialize + ?Sized>(secret_id: &str, value: &T) -> Result<String, FluffError> {
+    let value = serde_json::to_string(value).map_err(|err| {
+        FluffError::new_u16(
+            500,
+            "SecretSerializationError",
+            "Value cannot be serialized as a JSON secret",
+            false,
+        )
+        .add_context(secret_id)
+        .add_context(&err.to_string())
+    })?;
+    put_secret(secret_id, SecretValue::String(value), &[]).await
+}
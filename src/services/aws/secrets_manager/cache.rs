@@ -0,0 +1,71 @@
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use serde::de::DeserializeOwned;
+
+use crate::errors::FluffError;
+use crate::services::aws::secrets_manager::{self, Secret};
+use crate::services::cache::TtlCache;
+
+static SHARED_CACHE: OnceLock<SecretCache> = OnceLock::new();
+
+// Caches Secrets Manager reads in memory for the lifetime of the Lambda container. There is a single
+// instance, `SecretCache::shared`, so that `put_secret` and `create_secret` can invalidate it
+#[derive(Debug)]
+pub struct SecretCache {
+    entries: TtlCache<(String, Option<String>), Secret>,
+}
+
+impl SecretCache {
+    fn new() -> SecretCache {
+        SecretCache {
+            entries: TtlCache::new("SecretNotFound"),
+        }
+    }
+
+    pub fn shared() -> &'static SecretCache {
+        SHARED_CACHE.get_or_init(SecretCache::new)
+    }
+
+    // Rotated secrets are picked up at most `ttl` after their rotation, 5 minutes by default
+    pub fn set_ttl(&self, ttl: Duration) {
+        self.entries.set_ttl(ttl);
+    }
+
+    pub fn set_not_found_ttl(&self, ttl: Duration) {
+        self.entries.set_not_found_ttl(ttl);
+    }
+
+    pub fn set_stale_ttl(&self, ttl: Duration) {
+        self.entries.set_stale_ttl(ttl);
+    }
+
+    pub async fn get_secret(&self, secret_id: &str, version_stage: Option<&str>) -> Result<Secret, FluffError> {
+        let key = (String::from(secret_id), version_stage.map(String::from));
+
+        self.entries
+            .get(key, None, secrets_manager::get_secret(secret_id, version_stage))
+            .await
+    }
+
+    // Invalidates every version stage of the secret
+    pub fn invalidate(&self, secret_id: &str) {
+        self.entries.retain(|(name, _)| name != secret_id);
+    }
+
+    pub fn clear(&self) {
+        self.entries.clear();
+    }
+}
+
+pub async fn get_cached_secret(secret_id: &str) -> Result<Secret, FluffError> {
+    SecretCache::shared().get_secret(secret_id, None).await
+}
+
+pub async fn get_cached_secret_string(secret_id: &str) -> Result<String, FluffError> {
+    get_cached_secret(secret_id).await?.into_string()
+}
+
+pub async fn get_cached_secret_json<T: DeserializeOwned>(secret_id: &str) -> Result<T, FluffError> {
+    get_cached_secret(secret_id).await?.into_json()
+}
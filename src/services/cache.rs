@@ -0,0 +1,199 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+use crate::errors::FluffError;
+
+#[derive(Debug, Clone)]
+enum Cached<V> {
+    Found(V),
+    NotFound(FluffError),
+}
+
+#[derive(Debug, Clone)]
+struct CacheEntry<V> {
+    value: Cached<V>,
+    expires_at: Instant,
+    // A found value can still be served until then if the backend fails
+    stale_until: Instant,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Ttls {
+    ttl: Duration,
+    not_found_ttl: Duration,
+    stale_ttl: Duration,
+}
+
+// In-memory cache of remote values, with negative caching of not-found errors and stale values
+// served while the backend fails. Used by `ParameterCache` and `SecretCache`
+#[derive(Debug)]
+pub struct TtlCache<K, V> {
+    // Name of the error meaning that the value does not exist, e.g. ParameterNotFound
+    not_found: &'static str,
+    ttls: Mutex<Ttls>,
+    entries: Mutex<HashMap<K, CacheEntry<V>>>,
+}
+
+impl<K, V> TtlCache<K, V>
+where
+    K: Eq + Hash + Clone,
+    V: Clone,
+{
+    pub fn new(not_found: &'static str) -> TtlCache<K, V> {
+        TtlCache {
+            not_found,
+            ttls: Mutex::new(Ttls {
+                ttl: Duration::from_secs(5 * 60),
+                not_found_ttl: Duration::from_secs(30),
+                stale_ttl: Duration::from_secs(60 * 60),
+            }),
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    // TTL settings apply to values fetched from now on, 5 minutes by default
+    pub fn set_ttl(&self, ttl: Duration) {
+        lock(&self.ttls).ttl = ttl;
+    }
+
+    // How long a missing value is remembered, it is also the delay between two refresh attempts
+    // while a stale value is served. 30 seconds by default
+    pub fn set_not_found_ttl(&self, ttl: Duration) {
+        lock(&self.ttls).not_found_ttl = ttl;
+    }
+
+    // How long after its expiry a value can be served when the backend cannot be reached, 1 hour by default
+    pub fn set_stale_ttl(&self, ttl: Duration) {
+        lock(&self.ttls).stale_ttl = ttl;
+    }
+
+    // Returns the cached value, or calls `fetch` once it expired. `ttl` overrides the default TTL
+    pub async fn get<F>(&self, key: K, ttl: Option<Duration>, fetch: F) -> Result<V, FluffError>
+    where
+        F: Future<Output = Result<V, FluffError>>,
+    {
+        let ttls = *lock(&self.ttls);
+        let now = Instant::now();
+        let cached = lock(&self.entries).get(&key).cloned();
+
+        if let Some(entry) = &cached {
+            if now < entry.expires_at {
+                return match &entry.value {
+                    Cached::Found(value) => Ok(value.clone()),
+                    Cached::NotFound(err) => Err(err.clone()),
+                };
+            }
+        }
+
+        match fetch.await {
+            Ok(value) => {
+                let ttl = ttl.unwrap_or(ttls.ttl);
+                self.store(key, Cached::Found(value.clone()), now + ttl, now + ttl + ttls.stale_ttl);
+                Ok(value)
+            }
+            Err(err) if err.error_name == self.not_found => {
+                let expires_at = now + ttls.not_found_ttl;
+                self.store(key, Cached::NotFound(err.clone()), expires_at, expires_at);
+                Err(err)
+            }
+            Err(err) => match cached {
+                Some(CacheEntry {
+                    value: Cached::Found(value),
+                    stale_until,
+                    ..
+                }) if now < stale_until => {
+                    self.store(key, Cached::Found(value.clone()), now + ttls.not_found_ttl, stale_until);
+                    Ok(value)
+                }
+                _ => Err(err),
+            },
+        }
+    }
+
+    // Keeps only the entries whose key matches
+    pub fn retain(&self, mut keep: impl FnMut(&K) -> bool) {
+        lock(&self.entries).retain(|key, _| keep(key));
+    }
+
+    pub fn clear(&self) {
+        lock(&self.entries).clear();
+    }
+
+    fn store(&self, key: K, value: Cached<V>, expires_at: Instant, stale_until: Instant) {
+        lock(&self.entries).insert(
+            key,
+            CacheEntry {
+                value,
+                expires_at,
+                stale_until,
+            },
+        );
+    }
+}
+
+// A panic while holding the lock cannot leave the maps inconsistent, so poisoning is ignored
+pub(crate) fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use futures::executor::block_on;
+
+    use super::TtlCache;
+    use crate::errors::FluffError;
+
+    fn not_found() -> FluffError {
+        FluffError::new_u16(404, "ValueNotFound", "", true)
+    }
+
+    fn outage() -> FluffError {
+        FluffError::new_u16(500, "Outage", "", true)
+    }
+
+    #[test]
+    fn values_are_cached_until_they_expire() {
+        let cache = TtlCache::new("ValueNotFound");
+
+        assert_eq!(block_on(cache.get("a", None, async { Ok(1) })).unwrap(), 1);
+        assert_eq!(block_on(cache.get("a", None, async { Ok(2) })).unwrap(), 1);
+        assert_eq!(block_on(cache.get("a", Some(Duration::ZERO), async { Ok(3) })).unwrap(), 1);
+
+        cache.set_ttl(Duration::ZERO);
+        cache.clear();
+        block_on(cache.get("a", None, async { Ok(4) })).unwrap();
+        assert_eq!(block_on(cache.get("a", None, async { Ok(5) })).unwrap(), 5);
+    }
+
+    #[test]
+    fn not_found_is_cached() {
+        let cache = TtlCache::<&str, i32>::new("ValueNotFound");
+
+        assert!(block_on(cache.get("a", None, async { Err(not_found()) })).is_err());
+        let err = block_on(cache.get("a", None, async { Ok(1) })).unwrap_err();
+        assert_eq!(err.error_name, "ValueNotFound");
+
+        cache.retain(|key| *key != "a");
+        assert_eq!(block_on(cache.get("a", None, async { Ok(1) })).unwrap(), 1);
+    }
+
+    #[test]
+    fn stale_values_are_served_during_outages() {
+        let cache = TtlCache::new("ValueNotFound");
+        cache.set_ttl(Duration::ZERO);
+
+        block_on(cache.get("a", None, async { Ok(1) })).unwrap();
+        assert_eq!(block_on(cache.get("a", None, async { Err(outage()) })).unwrap(), 1);
+
+        cache.set_stale_ttl(Duration::ZERO);
+        cache.set_not_found_ttl(Duration::ZERO);
+        cache.clear();
+        block_on(cache.get("a", None, async { Ok(1) })).unwrap();
+        assert_eq!(block_on(cache.get("a", None, async { Err(outage()) })).unwrap_err().error_name, "Outage");
+    }
+}
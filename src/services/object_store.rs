@@ -0,0 +1,185 @@
+use std::future::Future;
+
+use md5::{Digest, Md5};
+
+use crate::errors::FluffError;
+use crate::services::aws::s3::{ListObjectsRequest, ObjectListing, ObjectMetadata, ObjectSummary, PutObjectOptions};
+
+pub mod filesystem;
+pub mod memory;
+
+pub use crate::services::aws::s3::S3ObjectStore;
+pub use filesystem::FsObjectStore;
+pub use memory::MemoryObjectStore;
+
+// Storage of files in one bucket, reading a missing key fails with a 404 S3ObjectNotFound
+pub trait ObjectStore {
+    fn get(&self, object_key: &str) -> impl Future<Output = Result<Vec<u8>, FluffError>> + Send;
+
+    // Returns the ETag of the new object. ETags only identify a version of an object within one
+    // backend: S3 and the in-memory store use the MD5 of single part uploads, S3 multipart uploads
+    // and the filesystem store do not, so they must not be compared across backends
+    fn put(
+        &self,
+        object_key: &str,
+        data: Vec<u8>,
+        options: &PutObjectOptions,
+    ) -> impl Future<Output = Result<Option<String>, FluffError>> + Send;
+
+    // Deleting a key that does not exist succeeds
+    fn delete(&self, object_key: &str) -> impl Future<Output = Result<(), FluffError>> + Send;
+
+    fn head(&self, object_key: &str) -> impl Future<Output = Result<ObjectMetadata, FluffError>> + Send;
+
+    // The bucket of the request is replaced by the bucket of the store
+    fn list(&self, request: &ListObjectsRequest) -> impl Future<Output = Result<ObjectListing, FluffError>> + Send;
+}
+
+pub(crate) fn object_not_found(store: &str, object_key: &str) -> FluffError {
+    FluffError::new_u16(404, "S3ObjectNotFound", "S3 file does not exist", false)
+        .add_context(store)
+        .add_context(object_key)
+}
+
+// The ETag S3 gives to objects uploaded in one part
+pub(crate) fn content_etag(data: &[u8]) -> String {
+    format!("\"{:x}\"", Md5::digest(data))
+}
+
+// The key up to the first delimiter after the prefix, e.g. the "folder" of the key
+fn common_prefix<'a>(key: &'a str, prefix: &str, delimiter: Option<&str>) -> Option<&'a str> {
+    let (delimiter, rest) = (delimiter?, key.strip_prefix(prefix)?);
+    rest.find(delimiter).map(|index| &key[..prefix.len() + index + delimiter.len()])
+}
+
+// Applies the prefix, delimiter, limit and continuation token of a request to objects sorted by key,
+// like S3 does. The token of a truncated listing is the last key or common prefix it returned.
+pub(crate) fn list_sorted(
+    objects: impl Iterator<Item = ObjectSummary>,
+    request: &ListObjectsRequest,
+) -> Result<ObjectListing, FluffError> {
+    request.validate()?;
+    let prefix = request.key_prefix();
+    let delimiter = request.key_delimiter();
+    let mut listing = ObjectListing::default();
+    let mut last: Option<String> = None;
+
+    for object in objects.filter(|object| object.key.starts_with(prefix)) {
+        // Keys up to the token, and the keys grouped under it, were returned by the previous listing
+        if let Some(start) = request.start_token() {
+            let grouped = common_prefix(start, prefix, delimiter).is_some_and(|group| object.key.starts_with(group));
+            if object.key.as_str() <= start || grouped {
+                continue;
+            }
+        }
+
+        let group = common_prefix(&object.key, prefix, delimiter);
+        if group.is_some() && listing.common_prefixes.last().map(String::as_str) == group {
+            continue;
+        }
+        let count = listing.objects.len() + listing.common_prefixes.len();
+        if request.item_limit().is_some_and(|limit| count >= limit) {
+            listing.next_token = last;
+            break;
+        }
+
+        match group {
+            Some(group) => {
+                last = Some(String::from(group));
+                listing.common_prefixes.push(String::from(group));
+            }
+            None => {
+                last = Some(object.key.clone());
+                listing.objects.push(object);
+            }
+        }
+    }
+
+    Ok(listing)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn listing(keys: &[&str], request: &ListObjectsRequest) -> ObjectListing {
+        let objects = keys.iter().map(|key| ObjectSummary {
+            key: String::from(*key),
+            size: 0,
+            etag: None,
+            last_modified: None,
+        });
+        list_sorted(objects, request).unwrap()
+    }
+
+    fn keys(listing: &ObjectListing) -> Vec<&str> {
+        listing.objects.iter().map(|object| object.key.as_str()).collect()
+    }
+
+    const KEYS: [&str; 6] = ["a.txt", "docs/one.txt", "docs/sub/two.txt", "docs/three.txt", "images/cat.png", "z.txt"];
+
+    #[test]
+    fn prefix_and_delimiter() {
+        let request = ListObjectsRequest::new("bucket").delimiter("/");
+        let root = listing(&KEYS, &request);
+        assert_eq!(keys(&root), vec!["a.txt", "z.txt"]);
+        assert_eq!(root.common_prefixes, vec!["docs/", "images/"]);
+        assert_eq!(root.next_token, None);
+
+        let docs = listing(&KEYS, &request.prefix("docs/"));
+        assert_eq!(keys(&docs), vec!["docs/one.txt", "docs/three.txt"]);
+        assert_eq!(docs.common_prefixes, vec!["docs/sub/"]);
+    }
+
+    #[test]
+    fn limit_counts_common_prefixes() {
+        let request = ListObjectsRequest::new("bucket").delimiter("/").limit(2);
+        let first = listing(&KEYS, &request);
+        assert_eq!(keys(&first), vec!["a.txt"]);
+        assert_eq!(first.common_prefixes, vec!["docs/"]);
+        assert_eq!(first.next_token.as_deref(), Some("docs/"));
+
+        let second = listing(&KEYS, &request.clone().continuation_token("docs/"));
+        assert_eq!(keys(&second), vec!["z.txt"]);
+        assert_eq!(second.common_prefixes, vec!["images/"]);
+        assert_eq!(second.next_token, None);
+    }
+
+    #[test]
+    fn continuation_resumes_after_the_last_key() {
+        let request = ListObjectsRequest::new("bucket").limit(4);
+        let first = listing(&KEYS, &request);
+        assert_eq!(keys(&first), &KEYS[..4]);
+        assert_eq!(first.next_token.as_deref(), Some("docs/three.txt"));
+
+        let second = listing(&KEYS, &request.continuation_token("docs/three.txt"));
+        assert_eq!(keys(&second), &KEYS[4..]);
+        assert_eq!(second.next_token, None);
+    }
+
+    #[test]
+    fn exact_limit_has_no_token() {
+        let all = listing(&KEYS, &ListObjectsRequest::new("bucket").limit(KEYS.len()));
+        assert_eq!(all.objects.len(), KEYS.len());
+        assert_eq!(all.next_token, None);
+    }
+
+    #[test]
+    fn zero_limit_is_rejected() {
+        let objects = KEYS.iter().map(|key| ObjectSummary {
+            key: String::from(*key),
+            size: 0,
+            etag: None,
+            last_modified: None,
+        });
+        let err = list_sorted(objects, &ListObjectsRequest::new("bucket").limit(0)).unwrap_err();
+
+        assert_eq!(err.error_name, "InvalidListLimit");
+    }
+
+    #[test]
+    fn etag_is_the_md5_of_the_content() {
+        assert_eq!(content_etag(b""), "\"d41d8cd98f00b204e9800998ecf8427e\"");
+        assert_eq!(content_etag(b"hello"), "\"5d41402abc4b2a76b9719d911017c592\"");
+    }
+}
@@ -0,0 +1,247 @@
+use std::fs::Metadata;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use aws_sdk_s3::primitives::DateTime;
+use tokio::fs;
+
+use crate::errors::FluffError;
+use crate::services::aws::s3::{ListObjectsRequest, ObjectListing, ObjectMetadata, ObjectSummary, PutObjectOptions};
+use crate::services::object_store::{list_sorted, object_not_found, ObjectStore};
+
+// Stores each object as a file below a local directory, keys are relative paths.
+// Only the content is kept, content type, cache control and metadata are not persisted.
+pub struct FsObjectStore {
+    root: PathBuf,
+}
+
+impl FsObjectStore {
+    pub fn new(root: impl Into<PathBuf>) -> FsObjectStore {
+        FsObjectStore { root: root.into() }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn path(&self, object_key: &str) -> Result<PathBuf, FluffError> {
+        let valid = !object_key.is_empty()
+            && object_key
+                .split('/')
+                .all(|segment| !segment.is_empty() && segment != "." && segment != ".." && !segment.contains('\\'));
+        if !valid {
+            return Err(FluffError::new_u16(
+                400,
+                "InvalidObjectKey",
+                "Object keys must be relative paths without empty, `.` or `..` segments",
+                false,
+            )
+            .add_context(object_key));
+        }
+        Ok(self.root.join(object_key))
+    }
+
+    fn io_error(&self, object_key: &str, err: std::io::Error) -> FluffError {
+        match err.kind() {
+            ErrorKind::NotFound => object_not_found(&self.root.to_string_lossy(), object_key),
+            _ => FluffError::new_u16(500, "ObjectStoreIoError", "Local object store cannot be accessed", true)
+                .add_context(&self.root.to_string_lossy())
+                .add_context(object_key)
+                .add_context(&err.to_string()),
+        }
+    }
+
+    async fn metadata(&self, object_key: &str, path: &Path) -> Result<ObjectMetadata, FluffError> {
+        let metadata = fs::metadata(path).await.map_err(|err| self.io_error(object_key, err))?;
+        let modified = metadata.modified().map_err(|err| self.io_error(object_key, err))?;
+        Ok(ObjectMetadata {
+            size: metadata.len() as i64,
+            etag: Some(metadata_etag(&metadata)),
+            last_modified: Some(DateTime::from(modified)),
+            content_type: None,
+            cache_control: None,
+            metadata: Default::default(),
+        })
+    }
+
+    // Keys of every file below the root, in no particular order.
+    // Symbolic links to files are listed, symbolic links to directories are not followed.
+    async fn keys(&self) -> Result<Vec<String>, FluffError> {
+        let mut keys = Vec::new();
+        let mut directories = vec![self.root.clone()];
+
+        while let Some(directory) = directories.pop() {
+            let mut entries = match fs::read_dir(&directory).await {
+                Ok(entries) => entries,
+                Err(err) if err.kind() == ErrorKind::NotFound => continue,
+                Err(err) => return Err(self.io_error("", err)),
+            };
+            while let Some(entry) = entries.next_entry().await.map_err(|err| self.io_error("", err))? {
+                let path = entry.path();
+                let file_type = entry.file_type().await.map_err(|err| self.io_error("", err))?;
+                if file_type.is_dir() {
+                    directories.push(path);
+                    continue;
+                }
+                let is_file = file_type.is_file()
+                    || (file_type.is_symlink() && fs::metadata(&path).await.is_ok_and(|metadata| metadata.is_file()));
+                if let (true, Ok(relative)) = (is_file, path.strip_prefix(&self.root)) {
+                    let segments: Vec<String> = relative
+                        .components()
+                        .map(|component| component.as_os_str().to_string_lossy().into_owned())
+                        .collect();
+                    keys.push(segments.join("/"));
+                }
+            }
+        }
+        Ok(keys)
+    }
+}
+
+// Built from the size and modification time so that listing does not read every file,
+// it changes whenever the file is rewritten
+fn metadata_etag(metadata: &Metadata) -> String {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+    format!("\"{:x}-{:x}\"", metadata.len(), modified.as_nanos())
+}
+
+impl ObjectStore for FsObjectStore {
+    async fn get(&self, object_key: &str) -> Result<Vec<u8>, FluffError> {
+        let path = self.path(object_key)?;
+        fs::read(path).await.map_err(|err| self.io_error(object_key, err))
+    }
+
+    async fn put(&self, object_key: &str, data: Vec<u8>, _options: &PutObjectOptions) -> Result<Option<String>, FluffError> {
+        let path = self.path(object_key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await.map_err(|err| self.io_error(object_key, err))?;
+        }
+        fs::write(&path, &data).await.map_err(|err| self.io_error(object_key, err))?;
+        Ok(self.metadata(object_key, &path).await?.etag)
+    }
+
+    async fn delete(&self, object_key: &str) -> Result<(), FluffError> {
+        let path = self.path(object_key)?;
+        match fs::remove_file(path).await {
+            Err(err) if err.kind() != ErrorKind::NotFound => Err(self.io_error(object_key, err)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn head(&self, object_key: &str) -> Result<ObjectMetadata, FluffError> {
+        let path = self.path(object_key)?;
+        self.metadata(object_key, &path).await
+    }
+
+    async fn list(&self, request: &ListObjectsRequest) -> Result<ObjectListing, FluffError> {
+        let mut keys = self.keys().await?;
+        keys.sort();
+
+        let mut summaries = Vec::new();
+        for key in keys.into_iter().filter(|key| key.starts_with(request.key_prefix())) {
+            let metadata = self.metadata(&key, &self.root.join(&key)).await?;
+            summaries.push(ObjectSummary {
+                key,
+                size: metadata.size,
+                etag: metadata.etag,
+                last_modified: metadata.last_modified,
+            });
+        }
+        list_sorted(summaries.into_iter(), request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A fresh directory per test, removed when dropped
+    struct TempRoot(PathBuf);
+
+    impl TempRoot {
+        fn new(name: &str) -> TempRoot {
+            let root = std::env::temp_dir().join(format!("fluff-fs-store-{}-{}", std::process::id(), name));
+            let _ = std::fs::remove_dir_all(&root);
+            TempRoot(root)
+        }
+    }
+
+    impl Drop for TempRoot {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[tokio::test]
+    async fn put_get_head_delete() {
+        let root = TempRoot::new("crud");
+        let store = FsObjectStore::new(&root.0);
+
+        let etag = store.put("keys/private.pem", b"secret".to_vec(), &PutObjectOptions::new()).await.unwrap();
+        assert_eq!(store.get("keys/private.pem").await.unwrap(), b"secret");
+        let metadata = store.head("keys/private.pem").await.unwrap();
+        assert_eq!(metadata.size, 6);
+        assert_eq!(metadata.etag, etag);
+
+        store.delete("keys/private.pem").await.unwrap();
+        store.delete("keys/private.pem").await.unwrap();
+        let err = store.get("keys/private.pem").await.unwrap_err();
+        assert_eq!(err.http_code, 404);
+        assert_eq!(err.error_name, "S3ObjectNotFound");
+    }
+
+    #[tokio::test]
+    async fn invalid_keys_are_rejected() {
+        let root = TempRoot::new("invalid");
+        let store = FsObjectStore::new(&root.0);
+        for key in ["", "../outside", "a//b", "a/./b", "a\\b"] {
+            assert_eq!(store.get(key).await.unwrap_err().error_name, "InvalidObjectKey", "{}", key);
+        }
+    }
+
+    #[tokio::test]
+    async fn list_with_delimiter() {
+        let root = TempRoot::new("list");
+        let store = FsObjectStore::new(&root.0);
+        for key in ["a.txt", "docs/one.txt", "docs/sub/two.txt", "z.txt"] {
+            store.put(key, key.as_bytes().to_vec(), &PutObjectOptions::new()).await.unwrap();
+        }
+
+        let listing = store.list(&ListObjectsRequest::new("bucket").delimiter("/")).await.unwrap();
+        let keys: Vec<&str> = listing.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, vec!["a.txt", "z.txt"]);
+        assert_eq!(listing.common_prefixes, vec!["docs/"]);
+
+        let listing = store.list(&ListObjectsRequest::new("bucket").prefix("docs/")).await.unwrap();
+        let keys: Vec<&str> = listing.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, vec!["docs/one.txt", "docs/sub/two.txt"]);
+        assert_eq!(listing.objects[0].size, 12);
+    }
+
+    #[tokio::test]
+    async fn missing_root_lists_nothing() {
+        let root = TempRoot::new("missing");
+        let listing = FsObjectStore::new(&root.0).list(&ListObjectsRequest::new("bucket")).await.unwrap();
+        assert!(listing.objects.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn symlinked_directories_are_not_followed() {
+        let root = TempRoot::new("symlink");
+        let store = FsObjectStore::new(&root.0);
+        store.put("dir/file.txt", b"data".to_vec(), &PutObjectOptions::new()).await.unwrap();
+        // A link back to the root would recurse forever if it were followed
+        std::os::unix::fs::symlink(&root.0, root.0.join("dir/loop")).unwrap();
+        std::os::unix::fs::symlink(root.0.join("dir/file.txt"), root.0.join("link.txt")).unwrap();
+
+        let listing = store.list(&ListObjectsRequest::new("bucket")).await.unwrap();
+        let keys: Vec<&str> = listing.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, vec!["dir/file.txt", "link.txt"]);
+    }
+}
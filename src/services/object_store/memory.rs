@@ -0,0 +1,145 @@
+use std::collections::BTreeMap;
+use std::sync::{Mutex, MutexGuard};
+
+use aws_sdk_s3::primitives::DateTime;
+
+use crate::errors::FluffError;
+use crate::services::aws::s3::{ListObjectsRequest, ObjectListing, ObjectMetadata, ObjectSummary, PutObjectOptions};
+use crate::services::object_store::{content_etag, list_sorted, object_not_found, ObjectStore};
+
+type Object = (Vec<u8>, ObjectMetadata);
+
+// Keeps objects in memory, used to seed fixtures in tests
+#[derive(Default)]
+pub struct MemoryObjectStore {
+    objects: Mutex<BTreeMap<String, Object>>,
+}
+
+impl MemoryObjectStore {
+    pub fn new() -> MemoryObjectStore {
+        MemoryObjectStore::default()
+    }
+
+    // Panics if the store is poisoned, which cannot happen before it is shared
+    pub fn with_object(self, object_key: &str, data: &[u8]) -> Self {
+        self.objects
+            .lock()
+            .expect("in-memory object store poisoned")
+            .insert(String::from(object_key), object(data.to_vec(), &PutObjectOptions::new()));
+        self
+    }
+
+    fn objects(&self) -> Result<MutexGuard<'_, BTreeMap<String, Object>>, FluffError> {
+        self.objects.lock().map_err(|_| {
+            FluffError::new_u16(500, "ObjectStorePoisoned", "The in-memory object store is unusable", false)
+        })
+    }
+
+    pub fn len(&self) -> usize {
+        self.objects().map(|objects| objects.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn object(data: Vec<u8>, options: &PutObjectOptions) -> Object {
+    let metadata = ObjectMetadata {
+        size: data.len() as i64,
+        etag: Some(content_etag(&data)),
+        last_modified: Some(DateTime::from(std::time::SystemTime::now())),
+        content_type: options.content_type.clone(),
+        cache_control: options.cache_control.clone(),
+        metadata: options.metadata.clone(),
+    };
+    (data, metadata)
+}
+
+impl ObjectStore for MemoryObjectStore {
+    async fn get(&self, object_key: &str) -> Result<Vec<u8>, FluffError> {
+        match self.objects()?.get(object_key) {
+            Some((data, _)) => Ok(data.clone()),
+            None => Err(object_not_found("memory", object_key)),
+        }
+    }
+
+    async fn put(&self, object_key: &str, data: Vec<u8>, options: &PutObjectOptions) -> Result<Option<String>, FluffError> {
+        let object = object(data, options);
+        let etag = object.1.etag.clone();
+        self.objects()?.insert(String::from(object_key), object);
+        Ok(etag)
+    }
+
+    async fn delete(&self, object_key: &str) -> Result<(), FluffError> {
+        self.objects()?.remove(object_key);
+        Ok(())
+    }
+
+    async fn head(&self, object_key: &str) -> Result<ObjectMetadata, FluffError> {
+        match self.objects()?.get(object_key) {
+            Some((_, metadata)) => Ok(metadata.clone()),
+            None => Err(object_not_found("memory", object_key)),
+        }
+    }
+
+    async fn list(&self, request: &ListObjectsRequest) -> Result<ObjectListing, FluffError> {
+        let objects = self.objects()?;
+        let summaries = objects.iter().map(|(key, (_, metadata))| ObjectSummary {
+            key: key.clone(),
+            size: metadata.size,
+            etag: metadata.etag.clone(),
+            last_modified: metadata.last_modified,
+        });
+        list_sorted(summaries, request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use futures::executor::block_on;
+
+    use super::*;
+
+    #[test]
+    fn put_get_head_delete() {
+        let store = MemoryObjectStore::new();
+        let options = PutObjectOptions::new().content_type("text/plain").metadata("owner", "fluff");
+        let etag = block_on(store.put("notes.txt", b"hello".to_vec(), &options)).unwrap();
+        assert_eq!(etag.as_deref(), Some("\"5d41402abc4b2a76b9719d911017c592\""));
+
+        assert_eq!(block_on(store.get("notes.txt")).unwrap(), b"hello");
+        let metadata = block_on(store.head("notes.txt")).unwrap();
+        assert_eq!(metadata.size, 5);
+        assert_eq!(metadata.etag, etag);
+        assert_eq!(metadata.content_type.as_deref(), Some("text/plain"));
+        assert_eq!(metadata.metadata.get("owner").map(String::as_str), Some("fluff"));
+
+        block_on(store.delete("notes.txt")).unwrap();
+        block_on(store.delete("notes.txt")).unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn missing_object_is_not_found() {
+        let store = MemoryObjectStore::new();
+        let err = block_on(store.get("missing")).unwrap_err();
+        assert_eq!(err.http_code, 404);
+        assert_eq!(err.error_name, "S3ObjectNotFound");
+        assert_eq!(block_on(store.head("missing")).unwrap_err().http_code, 404);
+    }
+
+    #[test]
+    fn list_fixtures() {
+        let store = MemoryObjectStore::new()
+            .with_object("b/two", b"2")
+            .with_object("a", b"1")
+            .with_object("b/one", b"1");
+        assert_eq!(store.len(), 3);
+
+        let listing = block_on(store.list(&ListObjectsRequest::new("other").prefix("b/"))).unwrap();
+        let keys: Vec<&str> = listing.objects.iter().map(|object| object.key.as_str()).collect();
+        assert_eq!(keys, vec!["b/one", "b/two"]);
+        assert_eq!(listing.objects[0].size, 1);
+    }
+}
@@ -0,0 +1,29 @@
+use std::future::Future;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::FluffError;
+use crate::services::aws::dynamodb::{Condition, PrimaryKey, QueryRequest};
+
+pub mod dynamodb;
+pub mod memory;
+
+pub use dynamodb::DynamoRepository;
+pub use memory::MemoryRepository;
+
+// Storage of one kind of record, conditional writes fail with a 409 ConditionalCheckFailed
+pub trait Repository<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    fn get(&self, key: &PrimaryKey) -> impl Future<Output = Result<Option<T>, FluffError>> + Send;
+
+    fn put(&self, item: &T, condition: &Condition) -> impl Future<Output = Result<(), FluffError>> + Send;
+
+    // Returns the deleted record, or None if there was nothing to delete
+    fn delete(&self, key: &PrimaryKey, condition: &Condition) -> impl Future<Output = Result<Option<T>, FluffError>> + Send;
+
+    // The table of the request is replaced by the table of the repository
+    fn query(&self, request: &QueryRequest) -> impl Future<Output = Result<Vec<T>, FluffError>> + Send;
+}
@@ -0,0 +1,69 @@
+use std::marker::PhantomData;
+
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::FluffError;
+use crate::services::aws::dynamodb::{self, serialization, Condition, PrimaryKey, QueryRequest};
+use crate::services::repository::Repository;
+
+pub struct DynamoRepository<T> {
+    table: String,
+    consistent: bool,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> DynamoRepository<T> {
+    pub fn new(table: &str) -> DynamoRepository<T> {
+        DynamoRepository {
+            table: String::from(table),
+            consistent: false,
+            _marker: PhantomData,
+        }
+    }
+
+    pub fn consistent(mut self, consistent: bool) -> Self {
+        self.consistent = consistent;
+        self
+    }
+
+    pub fn table(&self) -> &str {
+        &self.table
+    }
+}
+
+impl<T> Repository<T> for DynamoRepository<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn get(&self, key: &PrimaryKey) -> Result<Option<T>, FluffError> {
+        dynamodb::get_item_opt(&self.table, key.to_attributes(), self.consistent)
+            .await?
+            .map(|item| item.into_struct())
+            .transpose()
+    }
+
+    async fn put(&self, item: &T, condition: &Condition) -> Result<(), FluffError> {
+        let item = serialization::to_item(item)?;
+
+        dynamodb::insert_item_with_condition(&self.table, item, condition).await?;
+        Ok(())
+    }
+
+    async fn delete(&self, key: &PrimaryKey, condition: &Condition) -> Result<Option<T>, FluffError> {
+        dynamodb::delete_item(&self.table, key.to_attributes(), condition, true)
+            .await?
+            .map(|item| item.into_struct())
+            .transpose()
+    }
+
+    async fn query(&self, request: &QueryRequest) -> Result<Vec<T>, FluffError> {
+        let request = request.clone().with_table(&self.table);
+
+        dynamodb::query(&request)
+            .await?
+            .into_iter()
+            .map(|item| item.into_struct())
+            .collect()
+    }
+}
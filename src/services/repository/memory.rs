@@ -0,0 +1,248 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashMap};
+use std::marker::PhantomData;
+use std::sync::{Mutex, MutexGuard};
+
+use aws_sdk_dynamodb::types::AttributeValue;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::FluffError;
+use crate::services::aws::dynamodb::expiring::is_expired;
+use crate::services::aws::dynamodb::expression::compare_values;
+use crate::services::aws::dynamodb::{error_from_code, serialization, Condition, PrimaryKey, QueryRequest};
+use crate::services::repository::Repository;
+
+type Item = HashMap<String, AttributeValue>;
+
+// Keeps records serialized as DynamoDB items so that conditions behave like on a real table
+pub struct MemoryRepository<T> {
+    key_names: Vec<String>,
+    items: Mutex<BTreeMap<PrimaryKey, Item>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> MemoryRepository<T> {
+    // The partition key, followed by the sort key if the table has one
+    pub fn new(key_names: &[&str]) -> MemoryRepository<T> {
+        MemoryRepository {
+            key_names: key_names.iter().map(|name| String::from(*name)).collect(),
+            items: Mutex::new(BTreeMap::new()),
+            _marker: PhantomData,
+        }
+    }
+
+    fn items(&self) -> Result<MutexGuard<'_, BTreeMap<PrimaryKey, Item>>, FluffError> {
+        self.items.lock().map_err(|_| {
+            FluffError::new_u16(500, "RepositoryPoisoned", "The in-memory repository is unusable", false)
+        })
+    }
+
+    fn key_of(&self, item: &Item) -> Result<PrimaryKey, FluffError> {
+        let key_names: Vec<&str> = self.key_names.iter().map(String::as_str).collect();
+        PrimaryKey::from_item(&key_names, item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.items().map(|items| items.len()).unwrap_or(0)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+fn check_condition(condition: &Condition, item: Option<&Item>) -> Result<(), FluffError> {
+    if condition.evaluate(item)? {
+        Ok(())
+    } else {
+        Err(error_from_code(Some("ConditionalCheckFailed"), "").add_context("memory"))
+    }
+}
+
+impl<T> Repository<T> for MemoryRepository<T>
+where
+    T: Serialize + DeserializeOwned + Send + Sync,
+{
+    async fn get(&self, key: &PrimaryKey) -> Result<Option<T>, FluffError> {
+        let item = self.items()?.get(key).cloned();
+
+        item.map(serialization::from_item).transpose()
+    }
+
+    async fn put(&self, item: &T, condition: &Condition) -> Result<(), FluffError> {
+        let item = serialization::to_item(item)?;
+        let key = self.key_of(&item)?;
+
+        let mut items = self.items()?;
+        check_condition(condition, items.get(&key))?;
+        items.insert(key, item);
+        Ok(())
+    }
+
+    async fn delete(&self, key: &PrimaryKey, condition: &Condition) -> Result<Option<T>, FluffError> {
+        let removed = {
+            let mut items = self.items()?;
+            check_condition(condition, items.get(key))?;
+            items.remove(key)
+        };
+
+        removed.map(serialization::from_item).transpose()
+    }
+
+    async fn query(&self, request: &QueryRequest) -> Result<Vec<T>, FluffError> {
+        let unsupported = |description: &str, context: &str| {
+            FluffError::new_u16(400, "UnsupportedQuery", description, false).add_context(context)
+        };
+        if let Some(filter) = request.filter_expression() {
+            return Err(unsupported("Filter expressions can only be evaluated by DynamoDB", filter));
+        }
+        if let Some(index_name) = request.index_name() {
+            return Err(unsupported("Indexes are not supported by the in-memory repository", index_name));
+        }
+        if let Some(attribute) = request.projected_attributes().iter().find(|attribute| attribute.contains('.')) {
+            return Err(unsupported("Only top-level attributes can be projected in memory", attribute));
+        }
+
+        let (partition_key, partition_value) = request.partition_key();
+        let sort_key = request.sort_key_condition();
+
+        let mut matching: Vec<Item> = self
+            .items()?
+            .values()
+            .filter(|item| item.get(partition_key) == Some(partition_value))
+            .filter(|item| !(request.skips_expired() && is_expired(item)))
+            .filter(|item| match sort_key {
+                Some((name, condition)) => item.get(name).is_some_and(|value| condition.matches(value)),
+                None => true,
+            })
+            .cloned()
+            .collect();
+
+        // DynamoDB returns the items of a partition ordered by sort key, numbers by value
+        if let Some(sort_key) = self.key_names.get(1) {
+            matching.sort_by(|left, right| match (left.get(sort_key), right.get(sort_key)) {
+                (Some(left), Some(right)) => compare_values(left, right).unwrap_or(Ordering::Equal),
+                _ => Ordering::Equal,
+            });
+        }
+        if request.is_descending() {
+            matching.reverse();
+        }
+        if let Some(limit) = request.item_limit() {
+            matching.truncate(limit);
+        }
+
+        let projection = request.projected_attributes();
+        if !projection.is_empty() {
+            for item in &mut matching {
+                item.retain(|name, _| projection.contains(name));
+            }
+        }
+
+        matching.into_iter().map(serialization::from_item).collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::types::AttributeValue;
+    use futures::executor::block_on;
+    use serde::{Deserialize, Serialize};
+
+    use super::MemoryRepository;
+    use crate::services::aws::dynamodb::{Condition, QueryRequest, SortKeyCondition};
+    use crate::services::repository::Repository;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct Score {
+        player: String,
+        round: u32,
+        #[serde(default)]
+        points: u32,
+    }
+
+    fn repository() -> MemoryRepository<Score> {
+        let repository = MemoryRepository::new(&["player", "round"]);
+        for (player, round) in [("a", 10), ("a", 9), ("a", 100), ("b", 1)] {
+            let score = Score {
+                player: String::from(player),
+                round,
+                points: round * 2,
+            };
+            block_on(repository.put(&score, &Condition::new())).unwrap();
+        }
+        repository
+    }
+
+    fn rounds(scores: Vec<Score>) -> Vec<u32> {
+        scores.into_iter().map(|score| score.round).collect()
+    }
+
+    fn query() -> QueryRequest {
+        QueryRequest::new("scores", "player", AttributeValue::S(String::from("a")))
+    }
+
+    #[test]
+    fn query_orders_by_numeric_sort_key() {
+        let repository = repository();
+
+        assert_eq!(rounds(block_on(repository.query(&query())).unwrap()), vec![9, 10, 100]);
+        assert_eq!(rounds(block_on(repository.query(&query().descending())).unwrap()), vec![100, 10, 9]);
+        assert_eq!(rounds(block_on(repository.query(&query().descending().limit(2))).unwrap()), vec![100, 10]);
+
+        let request = query().sort_key("round", SortKeyCondition::GreaterThan(AttributeValue::N(String::from("9"))));
+        assert_eq!(rounds(block_on(repository.query(&request)).unwrap()), vec![10, 100]);
+    }
+
+    #[test]
+    fn query_projects_attributes() {
+        let scores = block_on(repository().query(&query().projection(&["player", "round"]))).unwrap();
+
+        assert!(scores.iter().all(|score| score.points == 0));
+    }
+
+    #[test]
+    fn query_rejects_what_needs_dynamodb() {
+        let repository = repository();
+
+        for request in [query().index("by_points"), query().filter("#p > :p"), query().projection(&["a.b"])] {
+            let err = block_on(repository.query(&request)).unwrap_err();
+            assert_eq!(err.error_name, "UnsupportedQuery");
+        }
+    }
+
+    #[test]
+    fn expired_items_are_only_skipped_on_request() {
+        #[derive(Debug, Serialize, Deserialize)]
+        struct Session {
+            id: String,
+            expires_at: u64,
+        }
+
+        let repository = MemoryRepository::<Session>::new(&["id"]);
+        let session = Session {
+            id: String::from("s"),
+            expires_at: 1,
+        };
+        block_on(repository.put(&session, &Condition::new())).unwrap();
+        let request = QueryRequest::new("sessions", "id", AttributeValue::S(String::from("s")));
+
+        assert_eq!(block_on(repository.query(&request)).unwrap().len(), 1);
+        assert!(block_on(repository.query(&request.skip_expired())).unwrap().is_empty());
+    }
+
+    #[test]
+    fn conditional_writes_fail_with_409() {
+        let repository = repository();
+        let score = Score {
+            player: String::from("b"),
+            round: 1,
+            points: 0,
+        };
+
+        let err = block_on(repository.put(&score, &Condition::new().attribute_not_exists("player"))).unwrap_err();
+        assert_eq!(err.http_code, 409);
+        assert_eq!(err.error_name, "ConditionalCheckFailed");
+    }
+}
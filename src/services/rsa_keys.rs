@@ -1,9 +1,11 @@
 use std::env;
 use std::fs::read;
 
+use crate::config::FluffConfig;
 use crate::errors::FluffError;
-use crate::services::aws::s3;
-use crate::services::aws::BUCKET_PROD;
+use crate::services::aws::s3::S3ObjectStore;
+use crate::services::aws::secrets_manager;
+use crate::services::object_store::{FsObjectStore, ObjectStore};
 
 fn read_private_from_env() -> Result<Vec<u8>, FluffError> {
     match env::var("PRIVATE_KEY_CONTENT") {
@@ -37,9 +39,10 @@ fn read_private_from_file() -> Result<Vec<u8>, FluffError> {
     }
 }
 
-async fn read_private_from_s3() -> Result<Vec<u8>, FluffError> {
+// Reads the key stored at PRIVATE_KEY_S3_PATH in any object store
+pub async fn read_private_from_object_store(store: &impl ObjectStore) -> Result<Vec<u8>, FluffError> {
     match env::var("PRIVATE_KEY_S3_PATH") {
-        Ok(pem_file) => s3::read_object(BUCKET_PROD, &pem_file).await,
+        Ok(pem_file) => store.get(&pem_file).await,
         Err(_) => Err(FluffError::new_u16(
             500,
             "PrivateKeyMissing",
@@ -49,16 +52,34 @@ async fn read_private_from_s3() -> Result<Vec<u8>, FluffError> {
     }
 }
 
+// None when PRIVATE_KEY_SECRET_ID is not set, so that the other sources are only tried in that case
+async fn read_private_from_secrets_manager() -> Result<Option<Vec<u8>>, FluffError> {
+    match env::var("PRIVATE_KEY_SECRET_ID") {
+        Ok(secret_id) => Ok(Some(secrets_manager::get_cached_secret(&secret_id).await?.value.into_bytes())),
+        Err(_) => Ok(None),
+    }
+}
 
 pub async fn read_private_key() -> Result<Vec<u8>, FluffError> {
-    read_private_from_s3().await
-        .or_else(|_| read_private_from_env())
-        .or_else(|_| read_private_from_file())
+    if let Some(pem_content) = read_private_from_secrets_manager().await? {
+        return Ok(pem_content);
+    }
+    if env::var("PRIVATE_KEY_S3_PATH").is_err() {
+        return read_private_from_env().or_else(|_| read_private_from_file());
+    }
+    // A local directory replaces the S3 bucket when FLUFF_OBJECT_STORE_DIR is set. Configuration
+    // errors are returned as is, a failed read falls back to the environment and the file
+    let stored = match env::var("FLUFF_OBJECT_STORE_DIR") {
+        Ok(root) => read_private_from_object_store(&FsObjectStore::new(root)).await,
+        Err(_) => read_private_from_object_store(&S3ObjectStore::new(&FluffConfig::shared()?.bucket)).await,
+    };
+    stored.or_else(|err| {
+        read_private_from_env()
+            .or_else(|_| read_private_from_file())
+            .map_err(|_| err)
+    })
 }
 
-
-
-
 fn read_public_from_env() -> Result<Vec<u8>, FluffError> {
     match env::var("PUBLIC_KEY_CONTENT") {
         Ok(pub_content) => Ok(pub_content.into_bytes()),
@@ -91,9 +112,10 @@ fn read_public_from_file() -> Result<Vec<u8>, FluffError> {
     }
 }
 
-async fn read_public_from_s3() -> Result<Vec<u8>, FluffError> {
+// Reads the key stored at PUBLIC_KEY_S3_PATH in any object store
+pub async fn read_public_from_object_store(store: &impl ObjectStore) -> Result<Vec<u8>, FluffError> {
     match env::var("PUBLIC_KEY_S3_PATH") {
-        Ok(pub_file) => s3::read_object(BUCKET_PROD, &pub_file).await,
+        Ok(pub_file) => store.get(&pub_file).await,
         Err(_) => Err(FluffError::new_u16(
             500,
             "PublicKeyMissing",
@@ -103,8 +125,30 @@ async fn read_public_from_s3() -> Result<Vec<u8>, FluffError> {
     }
 }
 
+// None when PUBLIC_KEY_SECRET_ID is not set, so that the other sources are only tried in that case
+async fn read_public_from_secrets_manager() -> Result<Option<Vec<u8>>, FluffError> {
+    match env::var("PUBLIC_KEY_SECRET_ID") {
+        Ok(secret_id) => Ok(Some(secrets_manager::get_cached_secret(&secret_id).await?.value.into_bytes())),
+        Err(_) => Ok(None),
+    }
+}
+
 pub async fn read_public_key() -> Result<Vec<u8>, FluffError> {
-    read_public_from_s3().await
-        .or_else(|_| read_public_from_env())
-        .or_else(|_| read_public_from_file())
+    if let Some(pub_content) = read_public_from_secrets_manager().await? {
+        return Ok(pub_content);
+    }
+    if env::var("PUBLIC_KEY_S3_PATH").is_err() {
+        return read_public_from_env().or_else(|_| read_public_from_file());
+    }
+    // A local directory replaces the S3 bucket when FLUFF_OBJECT_STORE_DIR is set. Configuration
+    // errors are returned as is, a failed read falls back to the environment and the file
+    let stored = match env::var("FLUFF_OBJECT_STORE_DIR") {
+        Ok(root) => read_public_from_object_store(&FsObjectStore::new(root)).await,
+        Err(_) => read_public_from_object_store(&S3ObjectStore::new(&FluffConfig::shared()?.bucket)).await,
+    };
+    stored.or_else(|err| {
+        read_public_from_env()
+            .or_else(|_| read_public_from_file())
+            .map_err(|_| err)
+    })
 }
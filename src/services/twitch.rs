@@ -1,7 +1,9 @@
 use std::env;
 
+use crate::config::FluffConfig;
 use crate::errors::FluffError;
 use crate::models::twitch::{OAuthResponse, User};
+use crate::services::aws::secrets_manager;
 
 
 fn get_client_id() -> Result<String, FluffError> {
@@ -16,24 +18,28 @@ fn get_client_id() -> Result<String, FluffError> {
     }
 }
 
-fn get_client_secret() -> Result<String, FluffError> {
+// TWITCH_CLIENT_SECRET_ID names a Secrets Manager secret, TWITCH_CLIENT_SECRET holds the secret itself
+async fn get_client_secret() -> Result<String, FluffError> {
+    if let Ok(secret_id) = env::var("TWITCH_CLIENT_SECRET_ID") {
+        return secrets_manager::get_cached_secret_string(&secret_id).await;
+    }
     match env::var("TWITCH_CLIENT_SECRET") {
         Ok(client_secret) => Ok(client_secret),
         Err(_) => Err(FluffError::new_u16(
             500,
             "TwitchClientSecretMissing",
-            "Missing TWITCH_CLIENT_SECRET in environment variables",
+            "Missing TWITCH_CLIENT_SECRET_ID or TWITCH_CLIENT_SECRET in environment variables",
             true,
         )),
     }
 }
 
-fn get_redirect_uri() -> String {
-    env::var("TWITCH_REDIRECT_URI").unwrap_or_else(|_| "https://fluffevent.fr".to_string())
+fn get_redirect_uri() -> Result<String, FluffError> {
+    Ok(FluffConfig::shared()?.twitch_redirect_uri.clone())
 }
 
 pub async fn get_oauth_from_code(code: String) -> Result<OAuthResponse, FluffError> {
-    let url = get_redirect_uri();
+    let url = get_redirect_uri()?;
     let url = urlencoding::encode(&url);
 
     let client = reqwest::Client::new();
@@ -41,7 +47,7 @@ pub async fn get_oauth_from_code(code: String) -> Result<OAuthResponse, FluffErr
         .post("https://id.twitch.tv/oauth2/token")
         .form(&[
             ("client_id", get_client_id()?),
-            ("client_secret", get_client_secret()?),
+            ("client_secret", get_client_secret().await?),
             ("code", code),
             ("grant_type", String::from("authorization_code")),
             ("redirect_uri", url.into_owned()),
@@ -77,7 +83,7 @@ pub async fn get_oauth_for_app() -> Result<OAuthResponse, FluffError> {
         .post("https://id.twitch.tv/oauth2/token")
         .form(&[
             ("client_id", get_client_id()?),
-            ("client_secret", get_client_secret()?),
+            ("client_secret", get_client_secret().await?),
             ("grant_type", String::from("client_credentials")),
         ])
         .send()